@@ -0,0 +1,277 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use crate::{
+    dao::{account_dao, group_dao},
+    models::{
+        AccountData, AccountDetails, AccountImportData, AccountImportError, AccountImportResult,
+        AccountImportRow,
+    },
+    runtime_err::RunTimeError,
+};
+
+/// Most rows one import takes.
+pub const MAX_ROWS: usize = 10000;
+
+const IMPORT_FIELDS: [&str; 6] = ["email", "pwd", "fans", "username", "device", "group_id"];
+
+/// Header of an export, the last column only with credentials.
+const EXPORT_COLUMNS: [&str; 9] = [
+    "id",
+    "email",
+    "username",
+    "fans",
+    "device",
+    "group_id",
+    "status",
+    "status_reason",
+    "pwd",
+];
+
+fn bad_request(message: String) -> RunTimeError {
+    RunTimeError::BadRequest(message)
+}
+
+//rows as column name to trimmed value, empty values left out
+fn parse_rows(format: &str, content: &str) -> Result<Vec<HashMap<String, String>>, RunTimeError> {
+    let mut rows = Vec::new();
+    match format {
+        "csv" => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .flexible(true)
+                .from_reader(content.as_bytes());
+            let headers = reader
+                .headers()
+                .map_err(|err| bad_request(format!("invalid csv header: {}", err)))?
+                .clone();
+            for (index, record) in reader.records().enumerate() {
+                let record = record.map_err(|err| {
+                    bad_request(format!("invalid csv row {}: {}", index + 1, err))
+                })?;
+                rows.push(
+                    headers
+                        .iter()
+                        .zip(record.iter())
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(header, value)| (header.to_string(), value.to_string()))
+                        .collect(),
+                );
+            }
+        }
+        "json" => {
+            let objects: Vec<serde_json::Map<String, Value>> = serde_json::from_str(content)
+                .map_err(|err| bad_request(format!("invalid json content: {}", err)))?;
+            for object in objects {
+                rows.push(
+                    object
+                        .into_iter()
+                        .filter_map(|(key, value)| {
+                            let value = match value {
+                                Value::String(value) => value.trim().to_string(),
+                                Value::Number(value) => value.to_string(),
+                                _ => return None,
+                            };
+                            (!value.is_empty()).then_some((key, value))
+                        })
+                        .collect(),
+                );
+            }
+        }
+        _ => return Err(bad_request("format must be csv or json".to_string())),
+    }
+    if rows.len() > MAX_ROWS {
+        return Err(bad_request(format!(
+            "an import takes at most {} rows",
+            MAX_ROWS
+        )));
+    }
+    Ok(rows)
+}
+
+/// Validates the rows of an import and saves them when all of them pass and
+/// it is not a dry run. Emails and usernames must be new, also within the
+/// import, and group ids must exist.
+pub fn import(
+    conn: &Mutex<Connection>,
+    data: &AccountImportData,
+) -> Result<AccountImportResult, RunTimeError> {
+    if let Some(field) = data
+        .mapping
+        .keys()
+        .find(|field| !IMPORT_FIELDS.contains(&field.as_str()))
+    {
+        return Err(bad_request(format!("unknown field {} in mapping", field)));
+    }
+    let rows = parse_rows(data.format.trim(), &data.content)?;
+    let group_ids: HashSet<i32> = group_dao::list_all()?
+        .data
+        .iter()
+        .map(|group| group.id)
+        .collect();
+    let existing = account_dao::list_all()?.data;
+    let mut emails: HashSet<String> = existing
+        .iter()
+        .map(|account| account.email.to_lowercase())
+        .collect();
+    let mut usernames: HashSet<String> = existing
+        .iter()
+        .filter_map(|account| account.username.as_deref())
+        .filter(|username| !username.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut accounts = Vec::new();
+    let mut previews = Vec::new();
+    let mut errors = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index + 1;
+        let field = |name: &str| {
+            let column = data.mapping.get(name).map(String::as_str).unwrap_or(name);
+            row.get(column).cloned()
+        };
+        let mut messages = Vec::new();
+        let email = field("email").unwrap_or_default();
+        let pwd = field("pwd").unwrap_or_default();
+        let username = field("username").unwrap_or_default();
+        if email.is_empty() {
+            messages.push("email is required".to_string());
+        } else if !emails.insert(email.to_lowercase()) {
+            messages.push(format!("email {} already exists", email));
+        }
+        if pwd.is_empty() {
+            messages.push("pwd is required".to_string());
+        }
+        if !username.is_empty() && !usernames.insert(username.to_lowercase()) {
+            messages.push(format!("username {} already exists", username));
+        }
+        let fans = match field("fans").map(|fans| fans.parse::<i32>()) {
+            Some(Ok(fans)) => fans,
+            Some(Err(_)) => {
+                messages.push("fans must be a number".to_string());
+                0
+            }
+            None => 0,
+        };
+        let group_id = match field("group_id").map(|group_id| group_id.parse::<i32>()) {
+            Some(Ok(group_id)) => Some(group_id),
+            Some(Err(_)) => {
+                messages.push("group_id must be a number".to_string());
+                None
+            }
+            None => data.group_id,
+        };
+        if let Some(group_id) = group_id.filter(|group_id| !group_ids.contains(group_id)) {
+            messages.push(format!("group {} does not exist", group_id));
+        }
+        if !messages.is_empty() {
+            errors.push(AccountImportError {
+                row: row_number,
+                message: messages.join(", "),
+            });
+            continue;
+        }
+        let device = field("device").unwrap_or_default();
+        previews.push(AccountImportRow {
+            row: row_number,
+            email: email.clone(),
+            username: username.clone(),
+            fans,
+            device: device.clone(),
+            group_id: group_id.unwrap_or_default(),
+        });
+        accounts.push(AccountData {
+            id: None,
+            email,
+            pwd,
+            fans,
+            device: Some(device),
+            username: Some(username),
+            group_id,
+            max_posts_per_day: None,
+            max_posts_per_week: None,
+            min_post_spacing: None,
+        });
+    }
+    let dry_run = data.dry_run.unwrap_or(false);
+    let imported = if dry_run || !errors.is_empty() {
+        0
+    } else {
+        account_dao::save_all(conn, &accounts)?;
+        accounts.len()
+    };
+    Ok(AccountImportResult {
+        dry_run,
+        total: rows.len(),
+        imported,
+        accounts: previews,
+        errors,
+    })
+}
+
+fn export_values(account: &AccountDetails, credentials: bool) -> Vec<String> {
+    let mut values = vec![
+        account.id.to_string(),
+        account.email.clone(),
+        account.username.clone().unwrap_or_default(),
+        account.fans.to_string(),
+        account.device.clone().unwrap_or_default(),
+        account.group_id.unwrap_or_default().to_string(),
+        account.status.clone(),
+        account.status_reason.clone().unwrap_or_default(),
+    ];
+    if credentials {
+        values.push(account.pwd.clone());
+    }
+    values
+}
+
+/// Accounts as csv, with the header the import reads.
+pub fn export_csv(accounts: &[AccountDetails], credentials: bool) -> Result<String, RunTimeError> {
+    let columns = if credentials {
+        &EXPORT_COLUMNS[..]
+    } else {
+        &EXPORT_COLUMNS[..EXPORT_COLUMNS.len() - 1]
+    };
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut write = |record: Vec<String>| {
+        writer
+            .write_record(record)
+            .map_err(|err| RunTimeError::new(&err.to_string()))
+    };
+    write(columns.iter().map(|column| column.to_string()).collect())?;
+    for account in accounts {
+        write(export_values(account, credentials))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| RunTimeError::new(&err.to_string()))?;
+    String::from_utf8(bytes).map_err(|err| RunTimeError::new(&err.to_string()))
+}
+
+/// Accounts as json objects keyed like the csv columns.
+pub fn export_json(accounts: &[AccountDetails], credentials: bool) -> Vec<Value> {
+    accounts
+        .iter()
+        .map(|account| {
+            let mut object = json!({
+                "id": account.id,
+                "email": account.email,
+                "username": account.username,
+                "fans": account.fans,
+                "device": account.device,
+                "group_id": account.group_id.unwrap_or_default(),
+                "status": account.status,
+                "status_reason": account.status_reason,
+            });
+            if credentials {
+                object["pwd"] = json!(account.pwd);
+            }
+            object
+        })
+        .collect()
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use chrono::{NaiveDateTime, NaiveTime};
+
+use crate::{dao::group_dao, models::GroupDetails, runtime_err::RunTimeError};
+
+/// Global pause switch, stored in settings and exported to the env by `setup_env`.
+pub fn is_globally_paused() -> bool {
+    std::env::var("SCHEDULER_PAUSED").unwrap_or_default() == "1"
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+/// `windows` is a comma separated list of `HH:MM-HH:MM`, a window whose end
+/// is before its start wraps over midnight.
+fn in_maintenance_window(windows: &str, at: NaiveDateTime) -> bool {
+    let time = at.time();
+    windows.split(',').any(|window| {
+        let mut parts = window.split('-');
+        let start = parts.next().and_then(parse_time);
+        let end = parts.next().and_then(parse_time);
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => time >= start && time < end,
+            (Some(start), Some(end)) => time >= start || time < end,
+            _ => false,
+        }
+    })
+}
+
+/// `dates` is a comma separated list of `YYYY-MM-DD`.
+fn in_blackout_dates(dates: &str, at: NaiveDateTime) -> bool {
+    let day = at.format("%Y-%m-%d").to_string();
+    dates.split(',').any(|date| date.trim() == day)
+}
+
+/// Reason the automation of `group` must not run at `at`, None when it may.
+pub fn group_block_reason(group: &GroupDetails, at: NaiveDateTime) -> Option<String> {
+    if is_globally_paused() {
+        return Some("scheduler is paused".to_string());
+    }
+    if group.paused == 1 {
+        let resumed = group
+            .resume_time
+            .as_deref()
+            .and_then(|time| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").ok())
+            .map(|resume_time| at >= resume_time)
+            .unwrap_or(false);
+        if !resumed {
+            return Some("group is paused".to_string());
+        }
+    }
+    if let Some(dates) = &group.blackout_dates {
+        if in_blackout_dates(dates, at) {
+            return Some("blackout date".to_string());
+        }
+    }
+    if let Some(windows) = &group.maintenance_windows {
+        if in_maintenance_window(windows, at) {
+            return Some("maintenance window".to_string());
+        }
+    }
+    None
+}
+
+/// Ids of the groups whose jobs must not be handed to agents right now.
+pub fn blocked_group_ids() -> Result<HashSet<i32>, RunTimeError> {
+    let now = chrono::Local::now().naive_local();
+    let groups = group_dao::list_all()?.data;
+    Ok(groups
+        .into_iter()
+        .filter(|group| group_block_reason(group, now).is_some())
+        .map(|group| group.id)
+        .collect())
+}
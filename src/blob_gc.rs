@@ -0,0 +1,148 @@
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use actix::prelude::*;
+use actix_web::web;
+use rusqlite::Connection;
+
+use crate::{
+    blob_store,
+    dao::{blob_dao, material_dao, upload_session_dao},
+    database,
+    models::BlobGcReport,
+    runtime_err::RunTimeError,
+    storage, upload,
+};
+
+const GC_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+//resumable uploads without a chunk for this long are dropped
+const UPLOAD_SESSION_EXPIRE_HOURS: i64 = 24;
+//an upload stores its file before the material row is committed, younger
+//orphans may still be in flight
+const ORPHAN_GRACE: Duration = Duration::from_secs(24 * 3600);
+
+pub struct BlobGcActor {
+    pub conn: web::Data<Mutex<Connection>>,
+}
+impl Actor for BlobGcActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_later(Duration::from_secs(60), |actor, _ctx| actor.spawn_collect());
+        ctx.run_interval(GC_INTERVAL, |actor, _ctx| actor.spawn_collect());
+    }
+}
+impl BlobGcActor {
+    fn spawn_collect(&self) {
+        let conn = self.conn.clone();
+        actix_rt::spawn(async move {
+            let session_conn = conn.clone();
+            match web::block(move || expire_upload_sessions(&session_conn)).await {
+                Ok(Ok(count)) if count > 0 => log::info!("expired {} upload sessions", count),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("expire upload sessions failed with error: {}", e),
+                Err(e) => log::error!("expire upload sessions failed with error: {}", e),
+            }
+            match web::block(move || collect(&conn, false)).await {
+                Ok(Ok(report)) => log_report(&report),
+                Ok(Err(e)) => log::error!("blob gc failed with error: {}", e),
+                Err(e) => log::error!("blob gc failed with error: {}", e),
+            }
+        });
+    }
+}
+
+/// Removes abandoned resumable uploads and their partial files.
+fn expire_upload_sessions(conn: &Mutex<Connection>) -> Result<usize, RunTimeError> {
+    let ids = upload_session_dao::list_expired(UPLOAD_SESSION_EXPIRE_HOURS)?;
+    for id in &ids {
+        let _ = std::fs::remove_file(upload::session_path(id));
+        upload_session_dao::del(conn, id)?;
+    }
+    Ok(ids.len())
+}
+
+fn log_report(report: &BlobGcReport) {
+    log::info!(
+        "blob gc: {} recounted, {} blobs removed, {} orphaned files ({} removed)",
+        report.recounted,
+        report.removed_blobs.len(),
+        report.orphaned_files.len(),
+        report.removed_orphans
+    );
+    for path in &report.orphaned_files {
+        log::warn!("blob gc: orphaned file {}", path);
+    }
+    for id in &report.dangling_materials {
+        log::warn!("blob gc: material {} has no file", id);
+    }
+    for sha256 in &report.dangling_blobs {
+        log::warn!("blob gc: blob {} has no file", sha256);
+    }
+}
+
+/// Repairs ref counts, removes unreferenced blobs and orphaned files older
+/// than a day, and reports rows whose file is gone. With `dry_run` nothing
+/// is changed, the report lists what would be done.
+pub fn collect(conn: &Mutex<Connection>, dry_run: bool) -> Result<BlobGcReport, RunTimeError> {
+    let _lock = conn.lock();
+    let db = database::get_conn()?;
+    let mut report = BlobGcReport {
+        dry_run,
+        ..Default::default()
+    };
+    report.recounted = if dry_run {
+        blob_dao::count_miscounted(&db)?
+    } else {
+        blob_dao::recount(&db)?
+    };
+
+    let storage = storage::current();
+    let files = storage.list("material/")?;
+    let mut referenced = HashSet::new();
+    let mut unreferenced_blobs = Vec::new();
+    for blob in blob_dao::list_counted()? {
+        if blob.ref_count > 0 {
+            if !files.contains_key(&blob.path) {
+                report.dangling_blobs.push(blob.sha256.clone());
+            }
+            referenced.insert(blob.path);
+            continue;
+        }
+        if !dry_run {
+            blob_dao::del(&db, &blob.sha256)?;
+        }
+        report.removed_blobs.push(blob.sha256);
+        unreferenced_blobs.push(blob.path);
+    }
+    for material in material_dao::list(None, None, None, None)?.data {
+        if !files.contains_key(&material.name) {
+            report.dangling_materials.push(material.id);
+        }
+        referenced.insert(material.name);
+    }
+    for path in unreferenced_blobs {
+        if !dry_run && !referenced.contains(&path) {
+            blob_store::remove(&path);
+        }
+        referenced.insert(path);
+    }
+
+    for (path, modified) in files {
+        if referenced.contains(&path) {
+            continue;
+        }
+        let expired = SystemTime::now()
+            .duration_since(modified)
+            .is_ok_and(|elapsed| elapsed > ORPHAN_GRACE);
+        if !dry_run && expired {
+            blob_store::remove(&path);
+            report.removed_orphans += 1;
+        }
+        report.orphaned_files.push(path);
+    }
+    Ok(report)
+}
@@ -0,0 +1,28 @@
+use crate::{runtime_err::RunTimeError, storage, upload::UploadedFile};
+
+/// `material/<first two hex digits>/<sha256>.<ext>`, the storage key of a blob.
+pub fn blob_path(sha256: &str, extension: &str) -> String {
+    let extension = extension.to_lowercase();
+    if extension.is_empty() {
+        return format!("material/{}/{}", &sha256[..2], sha256);
+    }
+    format!("material/{}/{}.{}", &sha256[..2], sha256, extension)
+}
+
+/// Moves the upload to `path` unless a file with the same content is already
+/// stored there. Returns whether a new file was created.
+pub fn store(file: UploadedFile, path: &str) -> Result<bool, RunTimeError> {
+    let storage = storage::current();
+    if storage.exists(path)? {
+        return Ok(false);
+    }
+    storage.put_file(file.path(), path)?;
+    Ok(true)
+}
+
+pub fn remove(path: &str) {
+    match storage::current().delete(path) {
+        Ok(_) => log::info!("removed {}", path),
+        Err(e) => log::error!("remove {} failed with error: {}", path, e),
+    }
+}
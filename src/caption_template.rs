@@ -0,0 +1,415 @@
+use std::collections::HashSet;
+
+use rand::{seq::SliceRandom, thread_rng};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    models::{GroupDetails, MaterialDetails},
+    runtime_err::RunTimeError,
+};
+
+/// Longest caption TikTok accepts, in characters as the app counts them.
+pub const CAPTION_LIMIT: usize = 2200;
+
+const VARIABLES: [&str; 5] = ["group", "date", "username", "tags", "hashtags"];
+
+/// Values a caption template can refer to.
+#[derive(Debug, Default)]
+pub struct CaptionContext {
+    pub group: String,
+    //YYYY-MM-DD of the publish slot
+    pub date: String,
+    pub username: String,
+    //material tags
+    pub tags: Vec<String>,
+    //group topic followed by the material hashtags, without #
+    pub hashtags: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Variable(String),
+    //`{a|b|c}`, one option is rendered at random
+    Choice(Vec<Vec<Node>>),
+}
+
+fn template_error(template: &str, reason: &str) -> RunTimeError {
+    RunTimeError::BadRequest(format!(
+        "invalid caption template {:?}: {}",
+        template, reason
+    ))
+}
+
+//parses until the end of the input or, inside a block, until the closing brace
+fn parse_nodes(
+    template: &str,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    in_block: bool,
+) -> Result<Vec<Vec<Node>>, RunTimeError> {
+    let mut options = vec![Vec::new()];
+    let mut text = String::new();
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            None if in_block => return Err(template_error(template, "missing }")),
+            None => break,
+        };
+        match c {
+            //`\{`, `\}` and `\|` are literal
+            '\\' if matches!(chars.peek(), Some('{' | '}' | '|' | '\\')) => {
+                text.push(chars.next().unwrap());
+            }
+            '{' => {
+                let nodes = options.last_mut().unwrap();
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                let block = parse_nodes(template, chars, true)?;
+                if block.len() > 1 {
+                    nodes.push(Node::Choice(block));
+                    continue;
+                }
+                let name = match block[0].as_slice() {
+                    [Node::Text(name)] => name.trim().to_string(),
+                    _ => return Err(template_error(template, "empty or nested variable")),
+                };
+                if !VARIABLES.contains(&name.as_str()) {
+                    return Err(template_error(
+                        template,
+                        &format!("unknown variable {}", name),
+                    ));
+                }
+                nodes.push(Node::Variable(name));
+            }
+            '}' if in_block => break,
+            '}' => return Err(template_error(template, "unexpected }")),
+            '|' if in_block => {
+                if !text.is_empty() {
+                    options
+                        .last_mut()
+                        .unwrap()
+                        .push(Node::Text(std::mem::take(&mut text)));
+                }
+                options.push(Vec::new());
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        options.last_mut().unwrap().push(Node::Text(text));
+    }
+    Ok(options)
+}
+
+fn parse(template: &str) -> Result<Vec<Node>, RunTimeError> {
+    let mut chars = template.chars().peekable();
+    Ok(parse_nodes(template, &mut chars, false)?
+        .pop()
+        .unwrap_or_default())
+}
+
+//length of the text around the variables, the longest option of each choice
+fn static_length(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Text(text) => text.graphemes(true).count(),
+            Node::Variable(_) => 0,
+            Node::Choice(options) => options
+                .iter()
+                .map(|option| static_length(option))
+                .max()
+                .unwrap_or_default(),
+        })
+        .sum()
+}
+
+/// Checks a template for syntax errors, unknown variables and text that
+/// alone is longer than `CAPTION_LIMIT`.
+pub fn validate(template: &str) -> Result<(), RunTimeError> {
+    let length = static_length(&parse(template)?);
+    if length > CAPTION_LIMIT {
+        return Err(template_error(
+            template,
+            &format!(
+                "text is {} characters, at most {} fit in a caption",
+                length, CAPTION_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn hashtag_list(hashtags: &[String]) -> String {
+    hashtags
+        .iter()
+        .map(|hashtag| format!("#{}", hashtag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    context: &CaptionContext,
+    out: &mut String,
+    uses_hashtags: &mut bool,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Variable(name) => match name.as_str() {
+                "group" => out.push_str(&context.group),
+                "date" => out.push_str(&context.date),
+                "username" => out.push_str(&context.username),
+                "tags" => out.push_str(&context.tags.join(" ")),
+                _ => {
+                    *uses_hashtags = true;
+                    out.push_str(&hashtag_list(&context.hashtags));
+                }
+            },
+            Node::Choice(options) => {
+                if let Some(option) = options.choose(&mut thread_rng()) {
+                    render_nodes(option, context, out, uses_hashtags);
+                }
+            }
+        }
+    }
+}
+
+/// Splits a topic or hashtag list on commas and whitespace, without `#`.
+pub fn split_hashtags(hashtags: &str) -> Vec<String> {
+    hashtags
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|hashtag| hashtag.trim_start_matches('#'))
+        .filter(|hashtag| !hashtag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+//lowercase hashtags of a caption, without #
+fn caption_hashtags(caption: &str) -> HashSet<String> {
+    caption
+        .split('#')
+        .skip(1)
+        .map(|rest| {
+            rest.chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|hashtag| !hashtag.is_empty())
+        .collect()
+}
+
+//drops whole trailing words, hashtags being last, until the caption fits
+fn fit_caption(caption: &mut String) {
+    while caption_length(caption) > CAPTION_LIMIT {
+        match caption.rfind(char::is_whitespace) {
+            Some(index) => {
+                caption.truncate(index);
+                caption.truncate(caption.trim_end().len());
+            }
+            None => {
+                //a single word longer than the limit, nothing to cut it at
+                *caption = caption.graphemes(true).take(CAPTION_LIMIT).collect();
+            }
+        }
+    }
+}
+
+/// Renders a template. Hashtags the template does not place with
+/// `{hashtags}` are appended when they fit in `CAPTION_LIMIT`, a caption
+/// still too long loses whole trailing hashtags and words.
+pub fn render(template: &str, context: &CaptionContext) -> Result<String, RunTimeError> {
+    let nodes = parse(template)?;
+    let mut caption = String::new();
+    let mut uses_hashtags = false;
+    render_nodes(&nodes, context, &mut caption, &mut uses_hashtags);
+    let mut caption = caption.trim().to_string();
+    if !uses_hashtags {
+        let present = caption_hashtags(&caption);
+        for hashtag in &context.hashtags {
+            let tag = format!("#{}", hashtag);
+            if present.contains(&hashtag.to_lowercase()) {
+                continue;
+            }
+            let len = caption.graphemes(true).count() + tag.graphemes(true).count() + 1;
+            if len > CAPTION_LIMIT {
+                break;
+            }
+            if !caption.is_empty() {
+                caption.push(' ');
+            }
+            caption.push_str(&tag);
+        }
+    }
+    if caption_length(&caption) > CAPTION_LIMIT {
+        log::warn!("caption cut to {} characters", CAPTION_LIMIT);
+        fit_caption(&mut caption);
+    }
+    Ok(caption)
+}
+
+/// Length of a caption as counted against `CAPTION_LIMIT`.
+pub fn caption_length(caption: &str) -> usize {
+    caption.graphemes(true).count()
+}
+
+/// The context of a publish job of `username` in the slot starting at `start_time`.
+pub fn publish_context(
+    group: &GroupDetails,
+    material: Option<&MaterialDetails>,
+    username: &str,
+    start_time: &str,
+) -> CaptionContext {
+    let mut hashtags = split_hashtags(group.topic.as_deref().unwrap_or(""));
+    if let Some(material_hashtags) = material.and_then(|material| material.hashtags.as_deref()) {
+        for hashtag in split_hashtags(material_hashtags) {
+            if !hashtags.contains(&hashtag) {
+                hashtags.push(hashtag);
+            }
+        }
+    }
+    CaptionContext {
+        group: group.name.clone(),
+        date: start_time.get(..10).unwrap_or(start_time).to_string(),
+        username: username.to_string(),
+        tags: material
+            .and_then(|material| material.tags.as_deref())
+            .map(|tags| tags.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+        hashtags,
+    }
+}
+
+/// The template a publish job uses: the material caption when it has one,
+/// otherwise a random line of the group title.
+pub fn publish_template(group: &GroupDetails, material: Option<&MaterialDetails>) -> String {
+    if let Some(caption) = material.and_then(|material| material.caption.clone()) {
+        return caption;
+    }
+    let title = group.title.clone().unwrap_or_default();
+    let title_lines: Vec<&str> = title
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    title_lines
+        .choose(&mut thread_rng())
+        .map(|line| line.to_string())
+        .unwrap_or_default()
+}
+
+/// Renders the caption of a publish job, a template that does not parse is
+/// published as it is.
+pub fn publish_caption(
+    group: &GroupDetails,
+    material: Option<&MaterialDetails>,
+    context: &CaptionContext,
+) -> String {
+    let template = publish_template(group, material);
+    render(&template, context).unwrap_or_else(|e| {
+        log::warn!("group {} caption not rendered: {}", group.id, e);
+        template
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> CaptionContext {
+        CaptionContext {
+            group: "pets".to_string(),
+            date: "2024-05-01".to_string(),
+            username: "alice".to_string(),
+            tags: vec!["cat".to_string(), "funny".to_string()],
+            hashtags: vec!["cat".to_string(), "fyp".to_string()],
+        }
+    }
+
+    #[test]
+    fn renders_variables() {
+        let caption = render("{group} on {date} by @{ username } {tags}", &context()).unwrap();
+        assert_eq!(caption, "pets on 2024-05-01 by @alice cat funny #cat #fyp");
+    }
+
+    #[test]
+    fn escapes_are_literal() {
+        let caption = render(r"\{group\} a\|b \\ {hashtags}", &context()).unwrap();
+        assert_eq!(caption, r"{group} a|b \ #cat #fyp");
+    }
+
+    #[test]
+    fn choice_renders_one_option() {
+        for _ in 0..20 {
+            let caption = render("{hi|hello {username}} {hashtags}", &context()).unwrap();
+            assert!(
+                caption == "hi #cat #fyp" || caption == "hello alice #cat #fyp",
+                "{}",
+                caption
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "{}",
+            "{ }",
+            "{{group}}",
+            "{group",
+            "group}",
+            "{name}",
+            "{a|{b}",
+        ] {
+            assert!(validate(template).is_err(), "{}", template);
+        }
+        assert!(validate("{a|{group}|}").is_ok());
+    }
+
+    #[test]
+    fn rejects_static_text_over_the_limit() {
+        let text = "a".repeat(CAPTION_LIMIT);
+        assert!(validate(&format!("{} {{username}}", &text[1..])).is_ok());
+        assert!(validate(&format!("{}b", text)).is_err());
+        //the longest option counts
+        assert!(validate(&format!("{{a|{}}}", text)).is_ok());
+        assert!(validate(&format!("{{a|{}b}}", text)).is_err());
+    }
+
+    #[test]
+    fn appends_only_missing_hashtags() {
+        assert_eq!(render("my #Cat.", &context()).unwrap(), "my #Cat. #fyp");
+        //#cats is another hashtag than #cat
+        assert_eq!(
+            render("my #cats", &context()).unwrap(),
+            "my #cats #cat #fyp"
+        );
+    }
+
+    #[test]
+    fn drops_whole_hashtags_over_the_limit() {
+        let text = "a".repeat(CAPTION_LIMIT - 6);
+        assert_eq!(render(&text, &context()).unwrap(), format!("{} #cat", text));
+        let text = "a".repeat(CAPTION_LIMIT - 3);
+        assert_eq!(render(&text, &context()).unwrap(), text);
+        //placed hashtags are dropped whole too
+        let caption = render(&format!("{} {{hashtags}}", text), &context()).unwrap();
+        assert_eq!(caption, text);
+    }
+
+    #[test]
+    fn long_variables_are_cut_at_words() {
+        let mut context = context();
+        context.group = "word ".repeat(CAPTION_LIMIT);
+        let caption = render("{group}", &context).unwrap();
+        assert!(caption_length(&caption) <= CAPTION_LIMIT);
+        assert!(caption.ends_with("word"));
+        context.group = "a".repeat(CAPTION_LIMIT + 10);
+        assert_eq!(
+            caption_length(&render("{group}", &context).unwrap()),
+            CAPTION_LIMIT
+        );
+    }
+}
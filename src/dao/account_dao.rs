@@ -1,15 +1,80 @@
 use std::sync::Mutex;
 
 use crate::{database, models::AccountData, runtime_err::RunTimeError};
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
 
-use crate::models::{AccountDetails, AccountResponseData};
+use crate::models::{AccountDetails, AccountResponseData, AccountStatus, AccountStatusHistory};
 
+const ACCOUNT_COLUMNS: &str = "account.device, account.email, account.fans, account.group_id,
+    account.id, account.pwd, account.username, account.max_posts_per_day,
+    account.max_posts_per_week, account.min_post_spacing, account.status, account.status_reason,
+    account.status_time";
+
+fn map_row(row: &Row) -> Result<AccountDetails> {
+    Ok(AccountDetails {
+        device: row.get(0)?,
+        email: row.get(1)?,
+        fans: row.get(2)?,
+        group_id: row.get(3)?,
+        id: row.get(4)?,
+        pwd: row.get(5)?,
+        username: row.get(6)?,
+        max_posts_per_day: row.get(7)?,
+        max_posts_per_week: row.get(8)?,
+        min_post_spacing: row.get(9)?,
+        status: row.get(10)?,
+        status_reason: row.get(11)?,
+        status_time: row.get(12)?,
+    })
+}
+
+/// Fails with the account, credentials left out, that already has `email` or
+/// `username`. Both compare case insensitively and empty values never clash.
+fn ensure_unique(
+    conn: &Connection,
+    email: &str,
+    username: Option<&str>,
+    except_id: Option<i32>,
+) -> Result<(), RunTimeError> {
+    let email = email.trim();
+    let username = username.unwrap_or_default().trim();
+    let existing = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM account WHERE id != ?3 AND (
+                (?1 != '' AND email = ?1 COLLATE NOCASE)
+                OR (?2 != '' AND username = ?2 COLLATE NOCASE))
+                ORDER BY id LIMIT 1",
+                ACCOUNT_COLUMNS
+            ),
+            rusqlite::params![email, username, except_id.unwrap_or_default()],
+            map_row,
+        )
+        .optional()?;
+    let Some(existing) = existing else {
+        return Ok(());
+    };
+    let field = if !email.is_empty() && existing.email.eq_ignore_ascii_case(email) {
+        "email"
+    } else {
+        "username"
+    };
+    let mut record = serde_json::to_value(&existing)?;
+    if let Some(record) = record.as_object_mut() {
+        record.remove("pwd");
+    }
+    Err(RunTimeError::Duplicate(
+        format!("account {} already has this {}", existing.id, field),
+        record,
+    ))
+}
 pub fn save(conn: &Mutex<Connection>, data: AccountData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
+    ensure_unique(&conn, &data.email, data.username.as_deref(), None)?;
     conn.execute(
-        "INSERT INTO account (email, pwd, fans, device,group_id,username) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO account (email, pwd, fans, device,group_id,username,max_posts_per_day,
+        max_posts_per_week,min_post_spacing) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         rusqlite::params![
             data.email,
             data.pwd,
@@ -17,28 +82,44 @@ pub fn save(conn: &Mutex<Connection>, data: AccountData) -> Result<(), RunTimeEr
             data.device.unwrap_or_default(),
             data.group_id.unwrap_or_default(),
             data.username.unwrap_or_default(),
+            data.max_posts_per_day,
+            data.max_posts_per_week,
+            data.min_post_spacing,
         ],
     )?;
     Ok(())
 }
+/// Inserts all accounts or none of them.
+pub fn save_all(conn: &Mutex<Connection>, accounts: &[AccountData]) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    for data in accounts {
+        tx.execute(
+            "INSERT INTO account (email, pwd, fans, device, group_id, username)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                data.email,
+                data.pwd,
+                data.fans,
+                data.device.clone().unwrap_or_default(),
+                data.group_id.unwrap_or_default(),
+                data.username.clone().unwrap_or_default(),
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
 pub fn update(conn: &Mutex<Connection>, data: AccountData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
     //get by id
-    let mut stmt = conn.prepare(
-        "select device, email, fans, group_id, id, pwd, username from account where id = ?1",
-    )?;
-    let mut account_iter = stmt.query_map(rusqlite::params![data.id.unwrap()], |row| {
-        Ok(AccountDetails {
-            device: row.get(0)?,
-            email: row.get(1)?,
-            fans: row.get(2)?,
-            group_id: row.get(3)?,
-            id: row.get(4)?,
-            pwd: row.get(5)?,
-            username: row.get(6)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!(
+        "select {} from account where id = ?1",
+        ACCOUNT_COLUMNS
+    ))?;
+    let mut account_iter = stmt.query_map(rusqlite::params![data.id.unwrap()], map_row)?;
     let mut account = account_iter.next().unwrap().unwrap();
     if data.email != "" {
         account.email = data.email;
@@ -59,10 +140,26 @@ pub fn update(conn: &Mutex<Connection>, data: AccountData) -> Result<(), RunTime
     if data.username != None {
         account.username = data.username;
     }
+    if data.max_posts_per_day.is_some() {
+        account.max_posts_per_day = data.max_posts_per_day;
+    }
+    if data.max_posts_per_week.is_some() {
+        account.max_posts_per_week = data.max_posts_per_week;
+    }
+    if data.min_post_spacing.is_some() {
+        account.min_post_spacing = data.min_post_spacing;
+    }
+    ensure_unique(
+        &conn,
+        &account.email,
+        account.username.as_deref(),
+        Some(account.id),
+    )?;
 
     conn.execute(
         "UPDATE account SET device = ?1, email = ?2, fans = ?3, 
-         group_id = ?4, id = ?5, pwd = ?6, username = ?7 
+         group_id = ?4, id = ?5, pwd = ?6, username = ?7, max_posts_per_day = ?8,
+         max_posts_per_week = ?9, min_post_spacing = ?10
          WHERE id = ?5",
         rusqlite::params![
             account.device.unwrap_or_default(),
@@ -72,6 +169,9 @@ pub fn update(conn: &Mutex<Connection>, data: AccountData) -> Result<(), RunTime
             account.id,
             account.pwd,
             account.username.unwrap_or_default(),
+            account.max_posts_per_day,
+            account.max_posts_per_week,
+            account.min_post_spacing,
         ],
     )?;
     Ok(())
@@ -83,22 +183,13 @@ pub fn del(id: String) -> Result<(), RunTimeError> {
 }
 pub fn list_all() -> Result<AccountResponseData, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-        SELECT device, email, fans, group_id, id, pwd, username FROM account
+        SELECT {} FROM account
             ORDER BY id ASC;",
-    )?;
-    let account_iter = stmt.query_map((), |row| {
-        Ok(AccountDetails {
-            device: row.get(0)?,
-            email: row.get(1)?,
-            fans: row.get(2)?,
-            group_id: row.get(3)?,
-            id: row.get(4)?,
-            pwd: row.get(5)?,
-            username: row.get(6)?,
-        })
-    })?;
+        ACCOUNT_COLUMNS
+    ))?;
+    let account_iter = stmt.query_map((), map_row)?;
 
     let mut data = Vec::new();
     for account_result in account_iter {
@@ -111,24 +202,15 @@ pub fn list_all() -> Result<AccountResponseData, RunTimeError> {
 pub fn list_account_by_device(device: String) -> Result<AccountResponseData, RunTimeError> {
     let conn = database::get_conn()?;
 
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-    SELECT device, email, fans, group_id, id, pwd, username FROM account
+    SELECT {} FROM account
         WHERE device = ?1
         ORDER BY id DESC;",
-    )?;
+        ACCOUNT_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let account_iter = stmt.query_map(rusqlite::params![device], |row| {
-        Ok(AccountDetails {
-            device: row.get(0)?,
-            email: row.get(1)?,
-            fans: row.get(2)?,
-            group_id: row.get(3)?,
-            id: row.get(4)?,
-            pwd: row.get(5)?,
-            username: row.get(6)?,
-        })
-    })?;
+    let account_iter = stmt.query_map(rusqlite::params![device], map_row)?;
     for account_result in account_iter {
         data.push(account_result?);
     }
@@ -136,24 +218,15 @@ pub fn list_account_by_device(device: String) -> Result<AccountResponseData, Run
 }
 pub fn list_account_by_group_id(group_id: i32) -> Result<AccountResponseData, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-    SELECT device, email, fans, group_id, id, pwd, username FROM account
+    SELECT {} FROM account
         WHERE group_id = ?1
         ORDER BY id ASC;",
-    )?;
+        ACCOUNT_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let account_iter = stmt.query_map(rusqlite::params![group_id], |row| {
-        Ok(AccountDetails {
-            device: row.get(0)?,
-            email: row.get(1)?,
-            fans: row.get(2)?,
-            group_id: row.get(3)?,
-            id: row.get(4)?,
-            pwd: row.get(5)?,
-            username: row.get(6)?,
-        })
-    })?;
+    let account_iter = stmt.query_map(rusqlite::params![group_id], map_row)?;
     for account_result in account_iter {
         data.push(account_result?);
     }
@@ -164,27 +237,18 @@ pub fn list_auto_train_account_by_agent_ip(
 ) -> Result<AccountResponseData, RunTimeError> {
     let conn = database::get_conn()?;
 
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-    SELECT account.device, account.email, account.fans, account.group_id, 
-    account.id, account.pwd, account.username FROM account
+    SELECT {} FROM account
         left join device on account.device = device.serial
         left join `group` on account.group_id = `group`.id
         WHERE device.agent_ip = ?1 AND `group`.auto_train = 1 and device.online = 1
+        AND account.status = 'active'
         ORDER BY account.id ASC;",
-    )?;
+        ACCOUNT_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let account_iter = stmt.query_map(rusqlite::params![agent_ip], |row| {
-        Ok(AccountDetails {
-            device: row.get(0)?,
-            email: row.get(1)?,
-            fans: row.get(2)?,
-            group_id: row.get(3)?,
-            id: row.get(4)?,
-            pwd: row.get(5)?,
-            username: row.get(6)?,
-        })
-    })?;
+    let account_iter = stmt.query_map(rusqlite::params![agent_ip], map_row)?;
     for account_result in account_iter {
         data.push(account_result?);
     }
@@ -197,8 +261,11 @@ pub fn update_username(
 ) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
+    if !new_username.eq_ignore_ascii_case(old_username) {
+        ensure_unique(&conn, "", Some(new_username), None)?;
+    }
     conn.execute(
-        "UPDATE account SET username = ?1 WHERE username = ?2",
+        "UPDATE account SET username = ?1 WHERE username = ?2 COLLATE NOCASE",
         rusqlite::params![new_username, old_username],
     )?;
     Ok(())
@@ -212,7 +279,7 @@ pub fn update_username_device(
     let conn = database::get_conn().unwrap();
     let result = conn
         .execute(
-            "UPDATE account SET device = ?1 WHERE username = ?2",
+            "UPDATE account SET device = ?1 WHERE username = ?2 COLLATE NOCASE",
             rusqlite::params![device, username],
         )
         .unwrap();
@@ -227,6 +294,96 @@ pub fn update_username_device(
     }
     Ok(())
 }
+pub fn get_by_id(id: i32) -> Result<AccountDetails, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        &format!("SELECT {} FROM account WHERE id = ?1", ACCOUNT_COLUMNS),
+        rusqlite::params![id],
+        map_row,
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+pub fn get_by_username(username: &str) -> Result<AccountDetails, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM account WHERE username = ?1 ORDER BY id LIMIT 1",
+            ACCOUNT_COLUMNS
+        ),
+        rusqlite::params![username],
+        map_row,
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+/// Moves an account to `status` and records the change, `source` is agent
+/// or operator. Agents cannot touch a disabled account or disable one.
+/// Returns whether the status changed.
+pub fn update_status(
+    conn: &Mutex<Connection>,
+    id: i32,
+    status: AccountStatus,
+    reason: Option<&str>,
+    source: &str,
+) -> Result<bool, RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    let previous: String = tx
+        .query_row(
+            "SELECT status FROM account WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(RunTimeError::NotFound)?;
+    if source == "agent"
+        && (status == AccountStatus::Disabled
+            || AccountStatus::parse(&previous) == Some(AccountStatus::Disabled))
+    {
+        return Err(RunTimeError::Conflict(format!(
+            "account {} is disabled or enabled by an operator only",
+            id
+        )));
+    }
+    if previous == status.as_str() {
+        return Ok(false);
+    }
+    tx.execute(
+        "UPDATE account SET status = ?1, status_reason = ?2,
+        status_time = datetime('now','localtime') WHERE id = ?3",
+        rusqlite::params![status.as_str(), reason, id],
+    )?;
+    tx.execute(
+        "INSERT INTO account_status_history (account_id, status, previous_status, reason, source)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![id, status.as_str(), previous, reason, source],
+    )?;
+    tx.commit()?;
+    Ok(true)
+}
+pub fn list_status_history(account_id: i32) -> Result<Vec<AccountStatusHistory>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, status, previous_status, reason, source, create_time
+        FROM account_status_history WHERE account_id = ?1 ORDER BY id DESC",
+    )?;
+    let history = stmt
+        .query_map(rusqlite::params![account_id], |row| {
+            Ok(AccountStatusHistory {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                status: row.get(2)?,
+                previous_status: row.get(3)?,
+                reason: row.get(4)?,
+                source: row.get(5)?,
+                create_time: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<AccountStatusHistory>, _>>()?;
+    Ok(history)
+}
 pub fn count_all() -> Result<i32, RunTimeError> {
     let conn = database::get_conn()?;
     let mut stmt = conn.prepare("SELECT count(*) FROM account")?;
@@ -24,6 +24,7 @@ pub fn list_all() -> Result<AvatarResponseData, RunTimeError> {
             Ok(AvatarDetails {
                 id: row.get(0)?,
                 name: row.get(1)?,
+                url: None,
             })
         })?
         .collect::<Result<Vec<AvatarDetails>, _>>()?;
@@ -42,6 +43,7 @@ pub fn random_one() -> Result<AvatarDetails, RunTimeError> {
         Ok(AvatarDetails {
             id: row.get(0)?,
             name: row.get(1)?,
+            url: None,
         })
     })?;
     Ok(avatar)
@@ -0,0 +1,105 @@
+use rusqlite::{Connection, Result, Row};
+
+use crate::models::BlobDetails;
+use crate::{database, runtime_err::RunTimeError};
+
+fn map_row(row: &Row) -> Result<BlobDetails> {
+    Ok(BlobDetails {
+        sha256: row.get(0)?,
+        path: row.get(1)?,
+        size: row.get(2)?,
+        ref_count: row.get(3)?,
+    })
+}
+
+/// Adds a reference to the blob, creating it at `path` when it is new.
+/// Returns the path the blob is stored at.
+pub fn acquire(
+    conn: &Connection,
+    sha256: &str,
+    path: &str,
+    size: i64,
+) -> Result<String, RunTimeError> {
+    conn.execute(
+        "INSERT INTO blob (sha256, path, size, ref_count) VALUES (?1, ?2, ?3, 1)
+        ON CONFLICT(sha256) DO UPDATE SET ref_count = ref_count + 1",
+        rusqlite::params![sha256, path, size],
+    )?;
+    let path = conn.query_row(
+        "SELECT path FROM blob WHERE sha256 = ?1",
+        rusqlite::params![sha256],
+        |row| row.get(0),
+    )?;
+    Ok(path)
+}
+
+/// Drops a reference to the blob, the row is deleted with its last
+/// reference and its path is returned so the caller can remove the file.
+pub fn release(conn: &Connection, sha256: &str) -> Result<Option<String>, RunTimeError> {
+    conn.execute(
+        "UPDATE blob SET ref_count = ref_count - 1 WHERE sha256 = ?1",
+        rusqlite::params![sha256],
+    )?;
+    let mut stmt = conn.prepare("SELECT path FROM blob WHERE sha256 = ?1 AND ref_count <= 0")?;
+    let mut path_iter = stmt.query_map(rusqlite::params![sha256], |row| row.get(0))?;
+    if let Some(path) = path_iter.next() {
+        del(conn, sha256)?;
+        return Ok(Some(path?));
+    }
+    Ok(None)
+}
+
+/// Whether a file is still used by a blob or a material row.
+pub fn is_referenced(conn: &Connection, path: &str) -> Result<bool, RunTimeError> {
+    let count: i32 = conn.query_row(
+        "SELECT (SELECT count(*) FROM blob WHERE path = ?1) + (SELECT count(*) FROM material WHERE name = ?1)",
+        rusqlite::params![path],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Resets every ref_count to the number of materials using the blob,
+/// returns the number of corrected rows.
+pub fn recount(conn: &Connection) -> Result<usize, RunTimeError> {
+    let count = conn.execute(
+        "UPDATE blob SET ref_count = (SELECT count(*) FROM material WHERE material.sha256 = blob.sha256)
+        WHERE ref_count != (SELECT count(*) FROM material WHERE material.sha256 = blob.sha256)",
+        (),
+    )?;
+    Ok(count)
+}
+
+/// Blobs with the number of materials actually referencing them as ref_count.
+pub fn list_counted() -> Result<Vec<BlobDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT sha256, path, size,
+        (SELECT count(*) FROM material WHERE material.sha256 = blob.sha256) FROM blob",
+    )?;
+    let mut data = Vec::new();
+    let blob_iter = stmt.query_map((), map_row)?;
+    for blob in blob_iter {
+        data.push(blob?);
+    }
+    Ok(data)
+}
+
+/// Number of blobs whose ref_count `recount` would correct.
+pub fn count_miscounted(conn: &Connection) -> Result<usize, RunTimeError> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM blob
+        WHERE ref_count != (SELECT count(*) FROM material WHERE material.sha256 = blob.sha256)",
+        (),
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+pub fn del(conn: &Connection, sha256: &str) -> Result<(), RunTimeError> {
+    conn.execute(
+        "DELETE FROM blob WHERE sha256 = ?1",
+        rusqlite::params![sha256],
+    )?;
+    Ok(())
+}
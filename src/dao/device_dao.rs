@@ -83,6 +83,9 @@ pub fn list_online_device(
     Ok(DeviceResponseData { data: devices })
 }
 
+/// Registers a device an agent reports. Agents report their devices again on
+/// every connect, so a known serial is updated rather than answered with a
+/// duplicate error, returns whether the device is new.
 pub fn save(
     ddl_sender: &Arc<Mutex<Sender<DdlMessage>>>,
     device_data: DeviceData,
@@ -123,8 +126,10 @@ pub fn save(
         .lock()
         .unwrap()
         .send(DdlMessage {
+            //a registration racing another one for the serial updates it too
             sql: "INSERT INTO device (serial, online, agent_ip, master_ip, init)
-        VALUES (?1, ?2, ?3, ?4, ?5)"
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(serial) DO UPDATE SET online = excluded.online, agent_ip = excluded.agent_ip"
                 .to_string(),
             params: vec![
                 Value::Text(device_data.serial),
@@ -1,16 +1,81 @@
 use std::sync::Mutex;
 
-use crate::models::{GroupData, GroupDetails, GroupResponseData};
+use crate::models::{GroupData, GroupDetails, GroupPauseData, GroupResponseData};
 use crate::{database, runtime_err::RunTimeError};
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, Result, Row};
+
+const GROUP_COLUMNS: &str = "id, name, title, auto_publish, auto_train, publish_start_time,
+    train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration,topic,
+    max_attempts,retry_delay,retry_error_classes,publish_timeout,paused,resume_time,maintenance_windows,
+    blackout_dates,min_duration,max_duration,aspect_ratio,
+    selection_strategy,tag_weights,reuse_after_days,inventory_alert_days,requires_approval,
+    max_posts_per_day,max_posts_per_week,min_post_spacing";
+
+fn map_row(row: &Row) -> Result<GroupDetails> {
+    Ok(GroupDetails {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        title: row.get(2)?,
+        auto_publish: row.get(3)?,
+        auto_train: row.get(4)?,
+        publish_start_time: row.get(5)?,
+        train_start_time: row.get(6)?,
+        publish_type: row.get(7)?,
+        product_link: row.get(8)?,
+        floow_probable: row.get(9)?,
+        like_probable: row.get(10)?,
+        collect_probable: row.get(11)?,
+        train_duration: row.get(12)?,
+        topic: row.get(13)?,
+        max_attempts: row.get(14)?,
+        retry_delay: row.get(15)?,
+        retry_error_classes: row.get(16)?,
+        publish_timeout: row.get(17)?,
+        paused: row.get(18)?,
+        resume_time: row.get(19)?,
+        maintenance_windows: row.get(20)?,
+        blackout_dates: row.get(21)?,
+        min_duration: row.get(22)?,
+        max_duration: row.get(23)?,
+        aspect_ratio: row.get(24)?,
+        selection_strategy: row.get(25)?,
+        tag_weights: row.get(26)?,
+        reuse_after_days: row.get(27)?,
+        inventory_alert_days: row.get(28)?,
+        requires_approval: row.get(29)?,
+        max_posts_per_day: row.get(30)?,
+        max_posts_per_week: row.get(31)?,
+        min_post_spacing: row.get(32)?,
+        inventory: None,
+    })
+}
+
+fn list_where(condition: &str) -> Result<GroupResponseData, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM `group` {} ORDER BY id ASC",
+        GROUP_COLUMNS, condition
+    ))?;
+    let mut data = Vec::new();
+    let group_iter = stmt.query_map((), map_row)?;
+    for group in group_iter {
+        data.push(group?);
+    }
+    Ok(GroupResponseData { data })
+}
 
 pub fn save(conn: &Mutex<Connection>, data: GroupData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
     conn.execute(
         "INSERT INTO `group` (name, title,  auto_publish, auto_train, publish_start_time,
-            train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6,?7,?8,?9,?10,?11,?12)",
+            train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration,
+            max_attempts,retry_delay,retry_error_classes,publish_timeout,paused,resume_time,
+            maintenance_windows,blackout_dates,min_duration,max_duration,aspect_ratio,selection_strategy,
+            tag_weights,reuse_after_days,topic,inventory_alert_days,requires_approval,max_posts_per_day,
+            max_posts_per_week,min_post_spacing)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20,?21,?22,?23,?24,?25,?26,?27,
+            ?28,?29,?30,?31,?32)",
         rusqlite::params![
             data.name,
             data.title,
@@ -24,6 +89,26 @@ pub fn save(conn: &Mutex<Connection>, data: GroupData) -> Result<(), RunTimeErro
             data.like_probable,
             data.collect_probable,
             data.train_duration,
+            data.max_attempts.unwrap_or(3),
+            data.retry_delay.unwrap_or(60),
+            data.retry_error_classes,
+            data.publish_timeout.unwrap_or(1800),
+            data.paused.unwrap_or(0),
+            data.resume_time,
+            data.maintenance_windows,
+            data.blackout_dates,
+            data.min_duration,
+            data.max_duration,
+            data.aspect_ratio,
+            data.selection_strategy,
+            data.tag_weights,
+            data.reuse_after_days,
+            data.topic,
+            data.inventory_alert_days,
+            data.requires_approval,
+            data.max_posts_per_day,
+            data.max_posts_per_week,
+            data.min_post_spacing,
         ],
     )?;
     Ok(())
@@ -32,9 +117,23 @@ pub fn update(conn: &Mutex<Connection>, data: GroupData) -> Result<(), RunTimeEr
     let _lock = conn.lock();
     let conn = database::get_conn()?;
     conn.execute(
-        "UPDATE `group` SET name = ?1, title = ?2,auto_publish = ?3, auto_train = ?4, 
-        publish_start_time = ?5, train_start_time = ?6, publish_type = ?7, product_link = ?8, 
-        floow_probable = ?9, like_probable = ?10, collect_probable = ?11, train_duration=?12 WHERE id = ?13",
+        "UPDATE `group` SET name = ?1, title = ?2,auto_publish = ?3, auto_train = ?4,
+        publish_start_time = ?5, train_start_time = ?6, publish_type = ?7, product_link = ?8,
+        floow_probable = ?9, like_probable = ?10, collect_probable = ?11, train_duration=?12,
+        max_attempts = COALESCE(?14, max_attempts), retry_delay = COALESCE(?15, retry_delay),
+        retry_error_classes = COALESCE(?16, retry_error_classes),
+        publish_timeout = COALESCE(?17, publish_timeout), paused = COALESCE(?18, paused),
+        resume_time = COALESCE(?19, resume_time), maintenance_windows = COALESCE(?20, maintenance_windows),
+        blackout_dates = COALESCE(?21, blackout_dates), min_duration = COALESCE(?22, min_duration),
+        max_duration = COALESCE(?23, max_duration), aspect_ratio = COALESCE(?24, aspect_ratio),
+        selection_strategy = COALESCE(?25, selection_strategy), tag_weights = COALESCE(?26, tag_weights),
+        reuse_after_days = COALESCE(?27, reuse_after_days), topic = COALESCE(?28, topic),
+        inventory_alert_days = COALESCE(?29, inventory_alert_days),
+        requires_approval = COALESCE(?30, requires_approval),
+        max_posts_per_day = COALESCE(?31, max_posts_per_day),
+        max_posts_per_week = COALESCE(?32, max_posts_per_week),
+        min_post_spacing = COALESCE(?33, min_post_spacing)
+        WHERE id = ?13",
         rusqlite::params![
             data.name,
             data.title,
@@ -49,67 +148,60 @@ pub fn update(conn: &Mutex<Connection>, data: GroupData) -> Result<(), RunTimeEr
             data.collect_probable,
             data.train_duration,
             data.id,
+            data.max_attempts,
+            data.retry_delay,
+            data.retry_error_classes,
+            data.publish_timeout,
+            data.paused,
+            data.resume_time,
+            data.maintenance_windows,
+            data.blackout_dates,
+            data.min_duration,
+            data.max_duration,
+            data.aspect_ratio,
+            data.selection_strategy,
+            data.tag_weights,
+            data.reuse_after_days,
+            data.topic,
+            data.inventory_alert_days,
+            data.requires_approval,
+            data.max_posts_per_day,
+            data.max_posts_per_week,
+            data.min_post_spacing,
         ],
     )?;
     Ok(())
 }
-pub fn list_all() -> Result<GroupResponseData, RunTimeError> {
+pub fn update_pause(
+    conn: &Mutex<Connection>,
+    id: i32,
+    data: GroupPauseData,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare("SELECT id, name, title, auto_publish, auto_train, publish_start_time,
-    train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration,topic
-     FROM `group` ORDER BY id ASC")?;
-    let mut data = Vec::new();
-    let group_iter = stmt.query_map((), |row| {
-        Ok(GroupDetails {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            title: row.get(2)?,
-            auto_publish: row.get(3)?,
-            auto_train: row.get(4)?,
-            publish_start_time: row.get(5)?,
-            train_start_time: row.get(6)?,
-            publish_type: row.get(7)?,
-            product_link: row.get(8)?,
-            floow_probable: row.get(9)?,
-            like_probable: row.get(10)?,
-            collect_probable: row.get(11)?,
-            train_duration: row.get(12)?,
-            topic: row.get(13)?,
-        })
-    })?;
-    for group in group_iter {
-        data.push(group?);
-    }
-    Ok(GroupResponseData { data })
+    conn.execute(
+        "UPDATE `group` SET paused = ?1, resume_time = ?2 WHERE id = ?3",
+        rusqlite::params![data.paused, data.resume_time, id],
+    )?;
+    Ok(())
+}
+pub fn list_all() -> Result<GroupResponseData, RunTimeError> {
+    list_where("")
 }
 pub fn list_all_auto_publish() -> Result<GroupResponseData, RunTimeError> {
+    list_where("WHERE auto_publish = 1")
+}
+pub fn get_by_id(id: i32) -> Result<GroupDetails, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare("SELECT id, name, title, auto_publish, auto_train, publish_start_time,
-    train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration,topic
-     FROM `group` WHERE auto_publish = 1 ORDER BY id ASC")?;
-    let mut data = Vec::new();
-    let group_iter = stmt.query_map((), |row| {
-        Ok(GroupDetails {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            title: row.get(2)?,
-            auto_publish: row.get(3)?,
-            auto_train: row.get(4)?,
-            publish_start_time: row.get(5)?,
-            train_start_time: row.get(6)?,
-            publish_type: row.get(7)?,
-            product_link: row.get(8)?,
-            floow_probable: row.get(9)?,
-            like_probable: row.get(10)?,
-            collect_probable: row.get(11)?,
-            train_duration: row.get(12)?,
-            topic: row.get(13)?,
-        })
-    })?;
-    for group in group_iter {
-        data.push(group?);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM `group` WHERE id = ?1",
+        GROUP_COLUMNS
+    ))?;
+    let mut group_iter = stmt.query_map(rusqlite::params![id], map_row)?;
+    if let Some(group) = group_iter.next() {
+        return Ok(group?);
     }
-    Ok(GroupResponseData { data })
+    Err(RunTimeError::NotFound)
 }
 pub fn del(id: i32) -> Result<(), RunTimeError> {
     let conn = database::get_conn()?;
@@ -117,31 +209,5 @@ pub fn del(id: i32) -> Result<(), RunTimeError> {
     Ok(())
 }
 pub fn list_all_auto_train() -> Result<GroupResponseData, RunTimeError> {
-    let conn = database::get_conn()?;
-    let mut stmt = conn.prepare("SELECT id, name, title,  auto_publish, auto_train, publish_start_time,
-    train_start_time,publish_type,product_link, floow_probable, like_probable, collect_probable,train_duration,topic
-     FROM `group` WHERE auto_train = 1 ORDER BY id ASC")?;
-    let mut data = Vec::new();
-    let group_iter = stmt.query_map((), |row| {
-        Ok(GroupDetails {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            title: row.get(2)?,
-            auto_publish: row.get(3)?,
-            auto_train: row.get(4)?,
-            publish_start_time: row.get(5)?,
-            train_start_time: row.get(6)?,
-            publish_type: row.get(7)?,
-            product_link: row.get(8)?,
-            floow_probable: row.get(9)?,
-            like_probable: row.get(10)?,
-            collect_probable: row.get(11)?,
-            train_duration: row.get(12)?,
-            topic: row.get(13)?,
-        })
-    })?;
-    for group in group_iter {
-        data.push(group?);
-    }
-    Ok(GroupResponseData { data })
+    list_where("WHERE auto_train = 1")
 }
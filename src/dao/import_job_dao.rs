@@ -0,0 +1,160 @@
+use std::sync::Mutex;
+
+use crate::models::{ImportFileDetails, ImportJobDetails};
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
+
+const JOB_COLUMNS: &str = "id, source_type, source, group_id, map_subfolders, dedup, status, total,
+    processed, created, duplicates, failed, error, create_time, update_time";
+
+fn map_row(row: &Row) -> Result<ImportJobDetails> {
+    Ok(ImportJobDetails {
+        id: row.get(0)?,
+        source_type: row.get(1)?,
+        source: row.get(2)?,
+        group_id: row.get(3)?,
+        map_subfolders: row.get(4)?,
+        dedup: row.get(5)?,
+        status: row.get(6)?,
+        total: row.get(7)?,
+        processed: row.get(8)?,
+        created: row.get(9)?,
+        duplicates: row.get(10)?,
+        failed: row.get(11)?,
+        error: row.get(12)?,
+        create_time: row.get(13)?,
+        update_time: row.get(14)?,
+        files: Vec::new(),
+    })
+}
+
+pub fn save(
+    conn: &Mutex<Connection>,
+    source_type: &str,
+    source: &str,
+    group_id: i32,
+    map_subfolders: i32,
+    dedup: &str,
+) -> Result<i32, RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    conn.execute(
+        "INSERT INTO import_job (source_type, source, group_id, map_subfolders, dedup)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![source_type, source, group_id, map_subfolders, dedup],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+pub fn get_by_id(id: i32) -> Result<ImportJobDetails, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        &format!("SELECT {} FROM import_job WHERE id = ?1", JOB_COLUMNS),
+        rusqlite::params![id],
+        map_row,
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+pub fn list() -> Result<Vec<ImportJobDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM import_job ORDER BY id DESC LIMIT 200",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map([], map_row)?
+        .collect::<Result<Vec<ImportJobDetails>, _>>()?;
+    Ok(jobs)
+}
+pub fn list_files(job_id: i32) -> Result<Vec<ImportFileDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT path, group_id, status, material_id, message FROM import_file
+        WHERE job_id = ?1 ORDER BY id",
+    )?;
+    let files = stmt
+        .query_map(rusqlite::params![job_id], |row| {
+            Ok(ImportFileDetails {
+                path: row.get(0)?,
+                group_id: row.get(1)?,
+                status: row.get(2)?,
+                material_id: row.get(3)?,
+                message: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<ImportFileDetails>, _>>()?;
+    Ok(files)
+}
+/// Jobs a restart interrupted, they are run again from the start.
+pub fn list_unfinished() -> Result<Vec<i32>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare("SELECT id FROM import_job WHERE status IN (0, 1) ORDER BY id")?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i32>, _>>()?;
+    Ok(ids)
+}
+/// Marks a job running with `total` files and drops the report of an earlier attempt.
+pub fn start(conn: &Mutex<Connection>, id: i32, total: i32) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM import_file WHERE job_id = ?1",
+        rusqlite::params![id],
+    )?;
+    tx.execute(
+        "UPDATE import_job SET status = 1, total = ?1, processed = 0, created = 0, duplicates = 0,
+        failed = 0, error = NULL, update_time = datetime('now','localtime') WHERE id = ?2",
+        rusqlite::params![total, id],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+/// Records the result of one file and advances the progress counters.
+pub fn add_file(
+    conn: &Mutex<Connection>,
+    id: i32,
+    file: &ImportFileDetails,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO import_file (job_id, path, group_id, status, material_id, message)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            id,
+            file.path,
+            file.group_id,
+            file.status,
+            file.material_id,
+            file.message,
+        ],
+    )?;
+    let counter = match file.status.as_str() {
+        "created" => "created",
+        "failed" => "failed",
+        _ => "duplicates",
+    };
+    tx.execute(
+        &format!(
+            "UPDATE import_job SET processed = processed + 1, {0} = {0} + 1,
+            update_time = datetime('now','localtime') WHERE id = ?1",
+            counter
+        ),
+        rusqlite::params![id],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+pub fn finish(conn: &Mutex<Connection>, id: i32, error: Option<&str>) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    conn.execute(
+        "UPDATE import_job SET status = ?1, error = ?2, update_time = datetime('now','localtime')
+        WHERE id = ?3",
+        rusqlite::params![if error.is_some() { 3 } else { 2 }, error, id],
+    )?;
+    Ok(())
+}
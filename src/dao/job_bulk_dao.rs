@@ -0,0 +1,226 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use chrono::NaiveDateTime;
+use rusqlite::{types::Value, Connection, OptionalExtension, Result};
+
+use crate::models::{JobBulkAction, JobBulkData, JobBulkResult, JobFilter};
+use crate::{database, runtime_err::RunTimeError};
+
+fn bad_request(message: &str) -> RunTimeError {
+    RunTimeError::BadRequest(message.to_string())
+}
+
+//a date alone covers the whole day
+fn parse_bound(bound: &str, time_of_day: &str) -> Result<String, RunTimeError> {
+    let bound = bound.trim();
+    let bound = if bound.len() == 10 {
+        format!("{} {}", bound, time_of_day)
+    } else {
+        bound.to_string()
+    };
+    NaiveDateTime::parse_from_str(&bound, "%Y-%m-%d %H:%M:%S").map_err(|_| {
+        bad_request("start_from and start_to must be YYYY-MM-DD or YYYY-MM-DD HH:MM:SS")
+    })?;
+    Ok(bound)
+}
+
+//the WHERE clause of the jobs the filter and the action select
+fn where_clause(
+    filter: &JobFilter,
+    action: JobBulkAction,
+) -> Result<(String, Vec<Value>), RunTimeError> {
+    let mut clause = "WHERE 1=1".to_string();
+    let mut params: Vec<Value> = Vec::new();
+    if let Some(group_id) = filter.group_id {
+        clause.push_str(" AND group_id = ?");
+        params.push(group_id.into());
+    }
+    if let Some(account_id) = filter.account_id {
+        clause.push_str(" AND account_id = ?");
+        params.push(account_id.into());
+    }
+    if let Some(device) = &filter.device {
+        clause.push_str(" AND account_id IN (SELECT id FROM account WHERE device = ?)");
+        params.push(device.clone().into());
+    }
+    if let Some(status) = filter.status.as_ref().filter(|status| !status.is_empty()) {
+        let placeholders = vec!["?"; status.len()].join(",");
+        clause.push_str(&format!(" AND status IN ({})", placeholders));
+        params.extend(status.iter().map(|status| Value::from(*status)));
+    }
+    if let Some(start_from) = &filter.start_from {
+        clause.push_str(" AND start_time >= ?");
+        params.push(parse_bound(start_from, "00:00:00")?.into());
+    }
+    if let Some(start_to) = &filter.start_to {
+        clause.push_str(" AND start_time <= ?");
+        params.push(parse_bound(start_to, "23:59:59")?.into());
+    }
+    //jobs an action applies to, whatever the filter asks for. Canceled jobs
+    //were approved or never needed it, so a retry cannot skip a review
+    let statuses = match action {
+        JobBulkAction::Cancel => Some("0, 3, 5"),
+        JobBulkAction::Delete => None,
+        JobBulkAction::Reschedule | JobBulkAction::Reassign => Some("0, 5"),
+        JobBulkAction::Retry => Some("3, 4, 7"),
+    };
+    if let Some(statuses) = statuses {
+        clause.push_str(&format!(" AND status IN ({})", statuses));
+    }
+    Ok((clause, params))
+}
+
+//where a reschedule or reassign puts a job
+enum Move {
+    At(String),
+    By(i64),
+    To(i32),
+}
+
+impl Move {
+    //the account and start time of a job after the move, None when its start
+    //time cannot be shifted
+    fn target(&self, account_id: i32, start_time: &str) -> Option<(i32, String)> {
+        match self {
+            Move::At(start_time) => Some((account_id, start_time.clone())),
+            Move::By(offset_minutes) => {
+                NaiveDateTime::parse_from_str(start_time, "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|time| {
+                        let time = time + chrono::Duration::minutes(*offset_minutes);
+                        (account_id, time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    })
+            }
+            Move::To(target_account_id) => Some((*target_account_id, start_time.to_string())),
+        }
+    }
+}
+
+/// Runs a bulk operation on the `publish_job` or `train_job` table in one
+/// transaction. A dry run only reports the jobs it would change.
+/// A reschedule or reassign skips jobs whose target slot already has a live
+/// job, and a moved job keeps the slot it was planned for so the scheduler
+/// does not plan that slot again.
+pub fn run(
+    conn: &Mutex<Connection>,
+    table: &str,
+    data: &JobBulkData,
+) -> Result<JobBulkResult, RunTimeError> {
+    let action = JobBulkAction::parse(&data.action).ok_or_else(|| {
+        bad_request("action must be cancel, delete, reschedule, reassign or retry")
+    })?;
+    let (clause, params) = where_clause(&data.filter, action)?;
+    let statement = match action {
+        //a job still pending review is rejected rather than canceled
+        JobBulkAction::Cancel => format!(
+            "UPDATE {} SET status = CASE WHEN status = 5 THEN 6 ELSE 7 END,
+            next_retry_time = NULL {}",
+            table, clause
+        ),
+        JobBulkAction::Delete => format!("DELETE FROM {} {}", table, clause),
+        JobBulkAction::Retry => format!(
+            "UPDATE {} SET status = 0, attempts = 0, next_retry_time = NULL {}",
+            table, clause
+        ),
+        JobBulkAction::Reschedule | JobBulkAction::Reassign => String::new(),
+    };
+    let movement = match action {
+        JobBulkAction::Reschedule => match (&data.start_time, data.offset_minutes) {
+            (Some(start_time), None) => {
+                NaiveDateTime::parse_from_str(start_time, "%Y-%m-%d %H:%M:%S")
+                    .map_err(|_| bad_request("start_time must be YYYY-MM-DD HH:MM:SS"))?;
+                Some(Move::At(start_time.clone()))
+            }
+            (None, Some(offset_minutes)) => Some(Move::By(offset_minutes)),
+            _ => {
+                return Err(bad_request(
+                    "reschedule takes either start_time or offset_minutes",
+                ))
+            }
+        },
+        JobBulkAction::Reassign => Some(Move::To(
+            data.account_id
+                .ok_or_else(|| bad_request("reassign takes account_id"))?,
+        )),
+        _ => None,
+    };
+    let dry_run = data.dry_run.unwrap_or(false);
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    if let Some(Move::To(account_id)) = movement {
+        tx.query_row(
+            "SELECT id FROM account WHERE id = ?1",
+            rusqlite::params![account_id],
+            |row| row.get::<_, i32>(0),
+        )
+        .optional()?
+        .ok_or_else(|| bad_request("account_id does not exist"))?;
+    }
+    let mut ids = Vec::new();
+    let mut skipped = Vec::new();
+    if let Some(movement) = movement {
+        let jobs = tx
+            .prepare(&format!(
+                "SELECT id, account_id, start_time FROM {} {} ORDER BY id",
+                table, clause
+            ))?
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    row.get::<_, i32>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<(i32, i32, String)>, _>>()?;
+        //slots this call fills, a dry run does not write them
+        let mut claimed = HashSet::new();
+        for (id, account_id, start_time) in jobs {
+            let Some(target) = movement.target(account_id, &start_time) else {
+                skipped.push(id);
+                continue;
+            };
+            let taken: i32 = tx.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE id != ?1 AND account_id = ?2
+                    AND start_time = ?3 AND status NOT IN (6, 7)",
+                    table
+                ),
+                rusqlite::params![id, target.0, target.1],
+                |row| row.get(0),
+            )?;
+            if taken > 0 || !claimed.insert(target.clone()) {
+                skipped.push(id);
+                continue;
+            }
+            if !dry_run {
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET slot_account_id = COALESCE(slot_account_id, account_id),
+                        slot_time = COALESCE(slot_time, start_time), account_id = ?1,
+                        start_time = ?2 WHERE id = ?3",
+                        table
+                    ),
+                    rusqlite::params![target.0, target.1, id],
+                )?;
+            }
+            ids.push(id);
+        }
+    } else {
+        ids = tx
+            .prepare(&format!("SELECT id FROM {} {} ORDER BY id", table, clause))?
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))?
+            .collect::<Result<Vec<i32>, _>>()?;
+        if !dry_run && !ids.is_empty() {
+            tx.execute(&statement, rusqlite::params_from_iter(params))?;
+        }
+    }
+    tx.commit()?;
+    Ok(JobBulkResult {
+        action: data.action.trim().to_string(),
+        dry_run,
+        count: ids.len(),
+        ids,
+        skipped,
+    })
+}
@@ -1,29 +1,260 @@
 use std::sync::Mutex;
 
-use crate::{database, runtime_err::RunTimeError};
-use rusqlite::{Connection, Result};
+use crate::dao::blob_dao;
+use crate::{blob_store, database, runtime_err::RunTimeError, upload::UploadedFile};
+use rusqlite::{Connection, OptionalExtension, Result, Row, TransactionBehavior};
 
-use crate::models::{MaterialData, MaterialDetails, MaterialResponseData};
+use crate::models::{
+    DedupMode, MaterialCollectionDetails, MaterialData, MaterialDetails, MaterialDuplicateDetails,
+    MaterialMetaData, MaterialResponseData, MaterialUploadResult,
+};
 
-pub fn save(conn: &Mutex<Connection>, materials: Vec<MaterialData>) -> Result<(), RunTimeError> {
+const MATERIAL_COLUMNS: &str =
+    "id, name, md5, used, group_id, sha256, duration, width, height, codec,
+    bitrate, file_size, tags, collection, caption, hashtags";
+
+fn map_row(row: &Row) -> Result<MaterialDetails> {
+    Ok(MaterialDetails {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        md5: row.get(2)?,
+        used: row.get(3)?,
+        group_id: row.get(4)?,
+        sha256: row.get(5)?,
+        duration: row.get(6)?,
+        width: row.get(7)?,
+        height: row.get(8)?,
+        codec: row.get(9)?,
+        bitrate: row.get(10)?,
+        file_size: row.get(11)?,
+        tags: row.get(12)?,
+        collection: row.get(13)?,
+        caption: row.get(14)?,
+        hashtags: row.get(15)?,
+    })
+}
+
+/// Trims and lowercases a comma separated list and drops duplicates, `#`
+/// prefixes and empty entries. `None` when nothing is left.
+pub fn normalize_list(list: &str) -> Option<String> {
+    let mut items: Vec<String> = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim().trim_start_matches('#').trim().to_lowercase();
+        if !item.is_empty() && !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    (!items.is_empty()).then(|| items.join(","))
+}
+//an empty or blank field is stored as NULL
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn find_by_md5(
+    conn: &Connection,
+    group_id: i32,
+    md5: &str,
+) -> Result<Option<MaterialDetails>, RunTimeError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM material WHERE group_id = ?1 AND md5 = ?2 ORDER BY id ASC LIMIT 1",
+        MATERIAL_COLUMNS
+    ))?;
+    let mut material_iter = stmt.query_map(rusqlite::params![group_id, md5], map_row)?;
+    match material_iter.next() {
+        Some(material) => Ok(Some(material?)),
+        None => Ok(None),
+    }
+}
+
+/// Stores the uploaded files at the material names and saves the materials,
+/// a file whose md5 already exists in its group (or earlier in the same
+/// request) is handled according to `mode`. Nothing is saved when a
+/// duplicate is rejected.
+pub fn save(
+    conn: &Mutex<Connection>,
+    materials: Vec<(UploadedFile, MaterialData)>,
+    mode: DedupMode,
+) -> Result<Vec<MaterialUploadResult>, RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    //files are stored under the lock, so a failed save cannot remove a file
+    //another upload of the same content is about to reference
+    let mut created_paths = Vec::new();
+    let result = insert(&mut conn, materials, mode, &mut created_paths);
+    if result.is_err() {
+        let _ = remove_unreferenced(&conn, created_paths);
+    }
+    result
+}
+fn insert(
+    conn: &mut Connection,
+    materials: Vec<(UploadedFile, MaterialData)>,
+    mode: DedupMode,
+    created_paths: &mut Vec<String>,
+) -> Result<Vec<MaterialUploadResult>, RunTimeError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut results = Vec::new();
+    for (f, m) in materials {
+        let file_name = f.file_name.clone();
+        if let Some(existing) = find_by_md5(&tx, m.group_id, &m.md5)? {
+            if mode == DedupMode::Reject {
+                return Err(RunTimeError::Conflict(format!(
+                    "{} is a duplicate of material {} ({})",
+                    file_name, existing.id, existing.name
+                )));
+            }
+            let linked = mode == DedupMode::Link;
+            results.push(MaterialUploadResult {
+                file_name,
+                status: if linked { "linked" } else { "skipped" }.to_string(),
+                id: linked.then_some(existing.id),
+                name: linked.then_some(existing.name),
+                duplicate_of: Some(existing.id),
+            });
+            continue;
+        }
+        //files with the same content share one blob
+        if blob_store::store(f, &m.name)? {
+            created_paths.push(m.name.clone());
+        }
+        //a blob already holding the content wins over the freshly stored copy
+        let name = match &m.sha256 {
+            Some(sha256) => {
+                blob_dao::acquire(&tx, sha256, &m.name, m.file_size.unwrap_or_default())?
+            }
+            None => m.name,
+        };
+        tx.execute(
+            "INSERT INTO material (name, md5, group_id, sha256, duration, width, height, codec, bitrate,
+            file_size, tags, collection, caption, hashtags)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            rusqlite::params![
+                name,
+                m.md5,
+                m.group_id,
+                m.sha256,
+                m.duration,
+                m.width,
+                m.height,
+                m.codec,
+                m.bitrate,
+                m.file_size,
+                m.tags.as_deref().and_then(normalize_list),
+                non_empty(&m.collection),
+                non_empty(&m.caption),
+                m.hashtags.as_deref().and_then(normalize_list),
+            ],
+        )?;
+        results.push(MaterialUploadResult {
+            file_name,
+            status: "created".to_string(),
+            id: Some(tx.last_insert_rowid() as i32),
+            name: Some(name),
+            duplicate_of: None,
+        });
+    }
+    tx.commit()?;
+    Ok(results)
+}
+/// Marks the material `name` of `group_id` used or unused. Files are shared
+/// between groups, without a group the one of the latest publish job of the
+/// file is taken, or else the group it was first added to. Returns the group
+/// of the updated material.
+pub fn update(
+    conn: &Mutex<Connection>,
+    name: String,
+    group_id: Option<i32>,
+    used: i32,
+) -> Result<Option<i32>, RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
-    for m in materials {
+    let group_id = match group_id {
+        Some(group_id) => Some(group_id),
+        None => conn
+            .query_row(
+                "SELECT group_id FROM material WHERE name = ?1
+                ORDER BY group_id IN (
+                    SELECT group_id FROM publish_job WHERE material = ?1 ORDER BY id DESC LIMIT 1
+                ) DESC, id
+                LIMIT 1",
+                rusqlite::params![name],
+                |row| row.get::<_, Option<i32>>(0),
+            )
+            .optional()?
+            .flatten(),
+    };
+    if let Some(group_id) = group_id {
         conn.execute(
-            "INSERT INTO material (name, md5, group_id) VALUES (?1, ?2, ?3)",
-            rusqlite::params![m.name, m.md5, m.group_id],
+            "UPDATE material SET used = ?1 WHERE name = ?2 AND group_id = ?3",
+            rusqlite::params![used, name, group_id],
         )?;
     }
-    Ok(())
+    Ok(group_id)
+}
+pub fn get_by_id(id: i32) -> Result<MaterialDetails, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        &format!("SELECT {} FROM material WHERE id = ?1", MATERIAL_COLUMNS),
+        rusqlite::params![id],
+        map_row,
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+/// Whether the material `name` exists, in `group_id` when one is given.
+pub fn exists_by_name(name: &str, group_id: Option<i32>) -> Result<bool, RunTimeError> {
+    let conn = database::get_conn()?;
+    let count: i32 = conn.query_row(
+        "SELECT count(*) FROM material WHERE name = ?1 AND (?2 IS NULL OR group_id = ?2)",
+        rusqlite::params![name, group_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
 }
-pub fn update(conn: &Mutex<Connection>, name: String, used: i32) -> Result<(), RunTimeError> {
+/// Sets the fields given in `data`, an empty string clears a field.
+pub fn update_meta(
+    conn: &Mutex<Connection>,
+    id: i32,
+    data: &MaterialMetaData,
+) -> Result<MaterialDetails, RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
+    let mut material = conn
+        .query_row(
+            &format!("SELECT {} FROM material WHERE id = ?1", MATERIAL_COLUMNS),
+            rusqlite::params![id],
+            map_row,
+        )
+        .optional()?
+        .ok_or(RunTimeError::NotFound)?;
+    if let Some(tags) = &data.tags {
+        material.tags = normalize_list(tags);
+    }
+    if data.collection.is_some() {
+        material.collection = non_empty(&data.collection);
+    }
+    if data.caption.is_some() {
+        material.caption = non_empty(&data.caption);
+    }
+    if let Some(hashtags) = &data.hashtags {
+        material.hashtags = normalize_list(hashtags);
+    }
     conn.execute(
-        "UPDATE material SET used = ?1 WHERE name = ?2",
-        rusqlite::params![used, name],
+        "UPDATE material SET tags = ?1, collection = ?2, caption = ?3, hashtags = ?4 WHERE id = ?5",
+        rusqlite::params![
+            material.tags,
+            material.collection,
+            material.caption,
+            material.hashtags,
+            id
+        ],
     )?;
-    Ok(())
+    Ok(material)
 }
 pub fn count(used: Option<i32>, group_id: Option<i32>) -> Result<i32, RunTimeError> {
     let conn = database::get_conn()?;
@@ -52,79 +283,200 @@ pub fn count(used: Option<i32>, group_id: Option<i32>) -> Result<i32, RunTimeErr
 pub fn list(
     used: Option<i32>,
     group_id: Option<i32>,
+    tag: Option<String>,
+    collection: Option<String>,
 ) -> Result<MaterialResponseData, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut query = "
-    SELECT id,name, md5, used, group_id FROM material
-    "
-    .to_string();
+    let mut query = format!("SELECT {} FROM material WHERE 1=1", MATERIAL_COLUMNS);
     let mut params: Vec<rusqlite::types::Value> = Vec::new();
     if let Some(used_value) = used {
-        query.push_str(" WHERE used = ?1");
+        query.push_str(" AND used = ?");
         params.push(used_value.into());
     }
     if let Some(group_id_value) = group_id {
-        query.push_str(" WHERE group_id = ?2");
+        query.push_str(" AND group_id = ?");
         params.push(group_id_value.into());
     }
+    if let Some(tag) = tag.as_deref().and_then(normalize_list) {
+        //instr, `%` and `_` in a tag are no wildcards
+        query.push_str(" AND instr(',' || tags || ',', ',' || ? || ',') > 0");
+        params.push(tag.into());
+    }
+    if let Some(collection) = collection {
+        query.push_str(" AND collection = ?");
+        params.push(collection.trim().to_string().into());
+    }
     query.push_str(" ORDER BY id DESC");
     let mut stmt = conn.prepare(&query)?;
     let mut data = Vec::new();
-    let material_iter = stmt.query_map(rusqlite::params_from_iter(params), |row| {
-        Ok(MaterialDetails {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            md5: row.get(2)?,
-            used: row.get(3)?,
-            group_id: row.get(4)?,
-        })
-    })?;
+    let material_iter = stmt.query_map(rusqlite::params_from_iter(params), map_row)?;
     for material in material_iter {
         data.push(material?);
     }
     Ok(MaterialResponseData { data })
 }
-pub fn del(id: i32) -> Result<(), RunTimeError> {
-    let conn = database::get_conn()?;
-    conn.execute("DELETE FROM material WHERE id = ?1", rusqlite::params![id])?;
+/// Removes the files in `paths` that no blob or material uses anymore.
+fn remove_unreferenced(conn: &Connection, mut paths: Vec<String>) -> Result<(), RunTimeError> {
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        if !blob_dao::is_referenced(conn, &path)? {
+            blob_store::remove(&path);
+        }
+    }
     Ok(())
 }
-pub fn get_and_use_one(
-    conn: &Mutex<Connection>,
+pub fn del(conn: &Mutex<Connection>, id: i32) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let material = tx
+        .query_row(
+            &format!("SELECT {} FROM material WHERE id = ?1", MATERIAL_COLUMNS),
+            rusqlite::params![id],
+            map_row,
+        )
+        .optional()?;
+    let Some(material) = material else {
+        return Ok(());
+    };
+    tx.execute("DELETE FROM material WHERE id = ?1", rusqlite::params![id])?;
+    let mut paths = vec![material.name];
+    if let Some(sha256) = &material.sha256 {
+        paths.extend(blob_dao::release(&tx, sha256)?);
+    }
+    tx.commit()?;
+    remove_unreferenced(&conn, paths)
+}
+/// Materials of a group `account_id` may publish, oldest first: never
+/// published on that account, and unused or, when `reuse_after_days` is
+/// positive, not published on any account for that many days.
+pub fn list_candidates(
     group_id: i32,
-) -> Result<MaterialDetails, RunTimeError> {
-    let query_conn = database::get_conn()?;
-    let mut stmt = query_conn.prepare(
-        "
-    SELECT id,name, md5, used, group_id FROM material
-    WHERE used = 0 AND group_id = ?1
-    ORDER BY id ASC LIMIT 1
-    ",
+    account_id: i32,
+    reuse_after_days: i32,
+) -> Result<Vec<MaterialDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM material m WHERE group_id = ?1
+        AND NOT EXISTS (SELECT 1 FROM publish_history h WHERE h.material_id = m.id AND h.account_id = ?2)
+        AND (used = 0 OR (?3 > 0 AND NOT EXISTS (SELECT 1 FROM publish_history h
+            WHERE h.material_id = m.id AND h.create_time > datetime('now','localtime', '-' || ?3 || ' days'))))
+        ORDER BY id ASC",
+        MATERIAL_COLUMNS
+    ))?;
+    let materials = stmt
+        .query_map(
+            rusqlite::params![group_id, account_id, reuse_after_days],
+            map_row,
+        )?
+        .collect::<Result<Vec<MaterialDetails>, _>>()?;
+    Ok(materials)
+}
+/// The collection of the material a group handed out last.
+pub fn last_collection(group_id: i32) -> Result<Option<String>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let collection = conn
+        .query_row(
+            "SELECT m.collection FROM publish_history h JOIN material m ON m.id = h.material_id
+            WHERE h.group_id = ?1 ORDER BY h.id DESC LIMIT 1",
+            rusqlite::params![group_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(collection.flatten())
+}
+/// Marks a material used and records that `account_id` got it.
+pub fn use_for_account(
+    conn: &Mutex<Connection>,
+    material: &MaterialDetails,
+    account_id: i32,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE material SET used = 1 WHERE id = ?1",
+        rusqlite::params![material.id],
     )?;
-    let mut material_iter = stmt.query_map(rusqlite::params![group_id], |row| {
-        Ok(MaterialDetails {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            md5: row.get(2)?,
-            used: row.get(3)?,
-            group_id: row.get(4)?,
-        })
-    })?;
-    if let Some(material) = material_iter.next() {
-        let material = material?;
-        update(&conn, material.name.clone(), 1)?;
-        return Ok(material);
-    }
-    Err(RunTimeError::NotFound)
+    tx.execute(
+        "INSERT INTO publish_history (account_id, material_id, group_id) VALUES (?1, ?2, ?3)",
+        rusqlite::params![account_id, material.id, material.group_id],
+    )?;
+    tx.commit()?;
+    Ok(())
 }
-pub fn delete_all() -> Result<(), RunTimeError> {
+/// Collections in use with their material counts, per group.
+pub fn list_collections(
+    group_id: Option<i32>,
+) -> Result<Vec<MaterialCollectionDetails>, RunTimeError> {
     let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT group_id, collection, count(*), sum(CASE WHEN used = 0 THEN 1 ELSE 0 END)
+        FROM material WHERE collection IS NOT NULL AND (?1 IS NULL OR group_id = ?1)
+        GROUP BY group_id, collection ORDER BY group_id, collection",
+    )?;
+    let collections = stmt
+        .query_map(rusqlite::params![group_id], |row| {
+            Ok(MaterialCollectionDetails {
+                group_id: row.get(0)?,
+                collection: row.get(1)?,
+                count: row.get(2)?,
+                unused: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<MaterialCollectionDetails>, _>>()?;
+    Ok(collections)
+}
+pub fn delete_all(conn: &Mutex<Connection>) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut paths = Vec::new();
+    {
+        let mut stmt = tx.prepare("SELECT name FROM material UNION SELECT path FROM blob")?;
+        let path_iter = stmt.query_map((), |row| row.get(0))?;
+        for path in path_iter {
+            paths.push(path?);
+        }
+    }
     //truncate table
-    conn.execute("DELETE FROM material", rusqlite::params![])?;
+    tx.execute("DELETE FROM material", rusqlite::params![])?;
+    tx.execute("DELETE FROM blob", rusqlite::params![])?;
     //reset autoincrement
-    conn.execute(
+    tx.execute(
         "DELETE FROM sqlite_sequence WHERE name='material'",
         rusqlite::params![],
     )?;
-    Ok(())
+    tx.commit()?;
+    remove_unreferenced(&conn, paths)
+}
+/// Materials sharing an md5 within a group, oldest first.
+pub fn list_duplicates(
+    group_id: Option<i32>,
+) -> Result<Vec<MaterialDuplicateDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM material WHERE (group_id, md5) IN
+            (SELECT group_id, md5 FROM material GROUP BY group_id, md5 HAVING count(*) > 1)
+        AND (?1 IS NULL OR group_id = ?1)
+        ORDER BY group_id ASC, md5 ASC, id ASC",
+        MATERIAL_COLUMNS
+    ))?;
+    let mut data: Vec<MaterialDuplicateDetails> = Vec::new();
+    let material_iter = stmt.query_map(rusqlite::params![group_id], map_row)?;
+    for material in material_iter {
+        let material = material?;
+        match data.last_mut() {
+            Some(last) if last.group_id == material.group_id && last.md5 == material.md5 => {
+                last.materials.push(material)
+            }
+            _ => data.push(MaterialDuplicateDetails {
+                group_id: material.group_id,
+                md5: material.md5.clone(),
+                materials: vec![material],
+            }),
+        }
+    }
+    Ok(data)
 }
@@ -1,11 +1,19 @@
 pub(crate) mod account_dao;
 pub(crate) mod avatar_dao;
+pub(crate) mod blob_dao;
 pub(crate) mod comment_dao;
 pub(crate) mod data_analytics_dao;
 pub(crate) mod device_dao;
 pub(crate) mod dialog_watcher_dao;
 pub(crate) mod group_dao;
+pub(crate) mod import_job_dao;
+pub(crate) mod job_bulk_dao;
 pub(crate) mod material_dao;
 pub(crate) mod music_dao;
+pub(crate) mod notification_dao;
+pub(crate) mod publish_history_dao;
 pub(crate) mod publish_job_dao;
+pub(crate) mod published_video_dao;
+pub(crate) mod scheduler_run_dao;
 pub(crate) mod train_job_dao;
+pub(crate) mod upload_session_dao;
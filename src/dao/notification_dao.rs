@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+use crate::models::NotificationDetails;
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::{Connection, Result};
+
+/// Raises a notification unless an unread one of the same kind is already
+/// pending for the group. Returns whether a notification was added.
+pub fn raise(
+    conn: &Mutex<Connection>,
+    kind: &str,
+    group_id: Option<i32>,
+    message: &str,
+) -> Result<bool, RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    let pending: i32 = conn.query_row(
+        "SELECT count(*) FROM notification WHERE kind = ?1 AND group_id IS ?2 AND read = 0",
+        rusqlite::params![kind, group_id],
+        |row| row.get(0),
+    )?;
+    if pending > 0 {
+        return Ok(false);
+    }
+    conn.execute(
+        "INSERT INTO notification (kind, group_id, message) VALUES (?1, ?2, ?3)",
+        rusqlite::params![kind, group_id, message],
+    )?;
+    Ok(true)
+}
+pub fn list(unread: bool) -> Result<Vec<NotificationDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, kind, group_id, message, read, create_time FROM notification {}
+        ORDER BY id DESC LIMIT 200",
+        if unread { "WHERE read = 0" } else { "" }
+    ))?;
+    let notifications = stmt
+        .query_map([], |row| {
+            Ok(NotificationDetails {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                group_id: row.get(2)?,
+                message: row.get(3)?,
+                read: row.get(4)?,
+                create_time: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<NotificationDetails>, _>>()?;
+    Ok(notifications)
+}
+/// Marks one notification read, or all of them when `id` is none.
+pub fn mark_read(conn: &Mutex<Connection>, id: Option<i32>) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    let updated = conn.execute(
+        "UPDATE notification SET read = 1 WHERE (?1 IS NULL OR id = ?1)",
+        rusqlite::params![id],
+    )?;
+    if id.is_some() && updated == 0 {
+        return Err(RunTimeError::NotFound);
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::models::PublishHistoryDetails;
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::Result;
+
+/// Most recent entries first, optionally for one account or material.
+pub fn list(
+    account_id: Option<i32>,
+    material_id: Option<i32>,
+) -> Result<Vec<PublishHistoryDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, material_id, group_id, create_time FROM publish_history
+        WHERE (?1 IS NULL OR account_id = ?1) AND (?2 IS NULL OR material_id = ?2)
+        ORDER BY id DESC LIMIT 500",
+    )?;
+    let history = stmt
+        .query_map(rusqlite::params![account_id, material_id], |row| {
+            Ok(PublishHistoryDetails {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                material_id: row.get(2)?,
+                group_id: row.get(3)?,
+                create_time: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<PublishHistoryDetails>, _>>()?;
+    Ok(history)
+}
@@ -1,10 +1,11 @@
 use std::sync::Mutex;
 
 use crate::models::{
-    CountGroupByStatus, PublishJobData, PublishJobDetails, PublishJobResponseData,
+    CountGroupByStatus, PublishJobCompleteData, PublishJobData, PublishJobDetails,
+    PublishJobResponseData, StuckJobDetails,
 };
-use crate::{database, runtime_err::RunTimeError};
-use rusqlite::{Connection, Result};
+use crate::{automation_pause, database, retry_policy, runtime_err::RunTimeError};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
 
 pub fn save(conn: &Mutex<Connection>, job_data: PublishJobData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
@@ -25,31 +26,74 @@ pub fn save(conn: &Mutex<Connection>, job_data: PublishJobData) -> Result<(), Ru
     )?;
     Ok(())
 }
+const JOB_COLUMNS: &str = "publish_job.id,publish_job.material, publish_job.account_id, publish_job.title,
+    publish_job.status, publish_job.start_time,publish_job.end_time,account.device,publish_job.group_id,
+    publish_job.publish_type,publish_job.product_link,account.username,publish_job.remark,
+    publish_job.attempts,publish_job.last_error,publish_job.error_class,publish_job.next_retry_time,
+    publish_job.run_time,publish_job.video_id,publish_job.video_url,publish_job.publish_time,
+    publish_job.platform_message,publish_job.reviewer,publish_job.review_comment,publish_job.review_time";
+
+fn map_row(row: &Row) -> Result<PublishJobDetails> {
+    Ok(PublishJobDetails {
+        id: row.get(0)?,
+        material: row.get(1)?,
+        account_id: row.get(2)?,
+        title: row.get(3)?,
+        status: row.get(4)?,
+        start_time: row.get(5)?,
+        end_time: row.get(6)?,
+        device: row.get(7)?,
+        group_id: row.get(8)?,
+        publish_type: row.get(9)?,
+        product_link: row.get(10)?,
+        username: row.get(11)?,
+        remark: row.get(12)?,
+        attempts: row.get(13)?,
+        last_error: row.get(14)?,
+        error_class: row.get(15)?,
+        next_retry_time: row.get(16)?,
+        run_time: row.get(17)?,
+        material_url: None,
+        video_id: row.get(18)?,
+        video_url: row.get(19)?,
+        publish_time: row.get(20)?,
+        platform_message: row.get(21)?,
+        reviewer: row.get(22)?,
+        review_comment: row.get(23)?,
+        review_time: row.get(24)?,
+    })
+}
 pub fn update(conn: &Mutex<Connection>, job_data: PublishJobData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
     //get by id
-    let mut stmt = conn.prepare(
-        "select id,material, account_id, title, status, start_time, end_time, group_id,publish_type, product_link,remark from publish_job where id = ?1",
-    )?;
-    let mut job_iter = stmt.query_map(rusqlite::params![job_data.id.unwrap()], |row| {
-        Ok(PublishJobDetails {
-            id: row.get(0)?,
-            material: row.get(1)?,
-            account_id: row.get(2)?,
-            title: row.get(3)?,
-            status: row.get(4)?,
-            start_time: row.get(5)?,
-            end_time: row.get(6)?,
-            device: None,
-            group_id: row.get(7)?,
-            publish_type: row.get(8)?,
-            product_link: row.get(9)?,
-            remark: row.get(10)?,
-            username: None,
-        })
-    })?;
-    let mut job = job_iter.next().unwrap().unwrap();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM publish_job
+        left join account on publish_job.account_id = account.id
+        WHERE publish_job.id = ?1",
+        JOB_COLUMNS
+    ))?;
+    let mut job_iter = stmt.query_map(rusqlite::params![job_data.id.unwrap()], map_row)?;
+    let mut job = job_iter.next().ok_or(RunTimeError::NotFound)??;
+    let old_status = job.status;
+    //a job only runs once it is approved
+    if matches!(old_status, 5 | 6) && job_data.status.is_some_and(|status| status != old_status) {
+        return Err(RunTimeError::Conflict(format!(
+            "publish_job {} is {}",
+            job.id,
+            if old_status == 5 {
+                "pending review"
+            } else {
+                "rejected"
+            }
+        )));
+    }
+    let content_changed = job_data
+        .material
+        .as_ref()
+        .is_some_and(|material| *material != job.material)
+        || job_data.title.is_some() && job_data.title != job.title
+        || job_data.product_link.is_some() && job_data.product_link != job.product_link;
     if job_data.material != None {
         job.material = job_data.material.unwrap();
     }
@@ -76,9 +120,52 @@ pub fn update(conn: &Mutex<Connection>, job_data: PublishJobData) -> Result<(),
     if job_data.remark != None {
         job.remark = job_data.remark;
     }
+    //an approved job that can still run goes back to review when what it
+    //posts changes
+    if matches!(old_status, 0 | 3 | 4 | 7) && content_changed {
+        let requires_approval: Option<i32> = conn
+            .query_row(
+                "SELECT requires_approval FROM `group` WHERE id = ?1",
+                rusqlite::params![job.group_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        if requires_approval == Some(1) {
+            job.status = 5;
+        }
+    }
+    //picked up by agent, remember when it started running
+    if job.status == 1 && old_status != 1 {
+        job.run_time = Some(
+            chrono::Local::now()
+                .naive_local()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        );
+    }
+    //failed by agent, apply the group retry policy
+    if job.status == 3 && old_status != 3 {
+        job.attempts += 1;
+        job.last_error = job_data.last_error.or(job.remark.clone());
+        job.error_class = job_data.error_class;
+        let policy = retry_policy::policy_for_group(job.group_id);
+        job.next_retry_time = policy.next_retry_time(job.attempts, job.error_class.as_deref());
+        if job.next_retry_time.is_none() {
+            log::warn!(
+                "publish_job {} moved to dead letter after {} attempts, error_class: {:?}",
+                job.id,
+                job.attempts,
+                job.error_class
+            );
+            job.status = 4;
+        }
+    }
     conn.execute(
         "UPDATE publish_job SET material = ?1, account_id = ?2, title = ?3, 
-         status = ?4, start_time = ?5, end_time = ?6, group_id = ?7, publish_type = ?8, product_link = ?9, remark = ?11
+         status = ?4, start_time = ?5, end_time = ?6, group_id = ?7, publish_type = ?8, product_link = ?9, remark = ?11,
+         attempts = ?12, last_error = ?13, error_class = ?14, next_retry_time = ?15,
+         run_time = ?16
          WHERE id = ?10",
         rusqlite::params![
             job.material,
@@ -91,40 +178,217 @@ pub fn update(conn: &Mutex<Connection>, job_data: PublishJobData) -> Result<(),
             job.publish_type,
             job.product_link,
             job.id,
-            job.remark
+            job.remark,
+            job.attempts,
+            job.last_error,
+            job.error_class,
+            job.next_retry_time,
+            job.run_time
         ],
     )?;
 
     Ok(())
 }
+/// Marks a job published with the post the agent reported and records the
+/// post in `published_video`. Reporting again replaces the earlier result.
+/// Only pending, running or published jobs take a report, so a job pending
+/// review, rejected, canceled or dead lettered cannot be completed.
+pub fn complete(
+    conn: &Mutex<Connection>,
+    id: i32,
+    data: &PublishJobCompleteData,
+    publish_time: &str,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    let job = tx
+        .query_row(
+            &format!(
+                "SELECT {} FROM publish_job
+                left join account on publish_job.account_id = account.id
+                WHERE publish_job.id = ?1",
+                JOB_COLUMNS
+            ),
+            rusqlite::params![id],
+            map_row,
+        )
+        .optional()?
+        .ok_or(RunTimeError::NotFound)?;
+    if !matches!(job.status, 0..=2) {
+        return Err(RunTimeError::Conflict(format!(
+            "publish_job {} has status {} and cannot be completed",
+            job.id, job.status
+        )));
+    }
+    tx.execute(
+        "UPDATE publish_job SET status = 2, end_time = datetime('now','localtime'), video_id = ?1,
+        video_url = ?2, publish_time = ?3, platform_message = ?4, next_retry_time = NULL
+        WHERE id = ?5",
+        rusqlite::params![
+            data.video_id,
+            data.video_url,
+            publish_time,
+            data.message,
+            id
+        ],
+    )?;
+    let material_id: Option<i32> = tx
+        .query_row(
+            "SELECT id FROM material WHERE name = ?1 AND group_id = ?2 ORDER BY id DESC LIMIT 1",
+            rusqlite::params![job.material, job.group_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    tx.execute(
+        "INSERT INTO published_video (publish_job_id, account_id, username, group_id, material,
+        material_id, video_id, video_url, publish_time)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        ON CONFLICT(publish_job_id) DO UPDATE SET video_id = excluded.video_id,
+        video_url = excluded.video_url, publish_time = excluded.publish_time",
+        rusqlite::params![
+            id,
+            job.account_id,
+            job.username,
+            job.group_id,
+            job.material,
+            material_id,
+            data.video_id,
+            data.video_url,
+            publish_time,
+        ],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+/// Approves (back to status 0) or rejects (status 6) the jobs of `ids`
+/// that are pending review, returns how many were reviewed.
+pub fn review(
+    conn: &Mutex<Connection>,
+    ids: &[i32],
+    approve: bool,
+    reviewer: &str,
+    comment: Option<&str>,
+) -> Result<usize, RunTimeError> {
+    let _lock = conn.lock();
+    let mut conn = database::get_conn()?;
+    let tx = conn.transaction()?;
+    let mut reviewed = 0;
+    for id in ids {
+        reviewed += tx.execute(
+            "UPDATE publish_job SET status = ?1, reviewer = ?2, review_comment = ?3,
+            review_time = datetime('now','localtime') WHERE id = ?4 AND status = 5",
+            rusqlite::params![if approve { 0 } else { 6 }, reviewer, comment, id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(reviewed)
+}
+pub fn get_status(id: i32) -> Result<i32, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        "SELECT status FROM publish_job WHERE id = ?1",
+        rusqlite::params![id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+/// Jobs of an account that post or already posted, starting at or after
+/// `from` and before `to`. Dead, rejected and canceled jobs do not count.
+pub fn count_posts(account_id: i32, from: &str, to: &str) -> Result<i32, RunTimeError> {
+    let conn = database::get_conn()?;
+    let count = conn.query_row(
+        "SELECT count(*) FROM publish_job WHERE account_id = ?1 AND status IN (0, 1, 2, 3, 5)
+        AND start_time >= ?2 AND start_time < ?3",
+        rusqlite::params![account_id, from, to],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+/// Jobs of an account starting at or after `from`, newest first.
+pub fn list_by_account(
+    account_id: i32,
+    from: &str,
+) -> Result<Vec<PublishJobDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM publish_job
+        left join account on publish_job.account_id = account.id
+        WHERE publish_job.account_id = ?1 AND publish_job.start_time >= ?2
+        ORDER BY publish_job.start_time DESC, publish_job.id DESC",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map(rusqlite::params![account_id, from], map_row)?
+        .collect::<Result<Vec<PublishJobDetails>, _>>()?;
+    Ok(jobs)
+}
+/// Jobs starting between `from` and `to`, both included, by start time.
+pub fn list_between(
+    from: &str,
+    to: &str,
+    group_id: Option<i32>,
+) -> Result<Vec<PublishJobDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM publish_job
+        left join account on publish_job.account_id = account.id
+        WHERE publish_job.start_time >= ?1 AND publish_job.start_time <= ?2
+        AND (?3 IS NULL OR publish_job.group_id = ?3)
+        ORDER BY publish_job.start_time ASC, publish_job.id ASC",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map(rusqlite::params![from, to, group_id], map_row)?
+        .collect::<Result<Vec<PublishJobDetails>, _>>()?;
+    Ok(jobs)
+}
+/// Accounts and times of the slots jobs were moved away from, between `from`
+/// and `to`, both included.
+pub fn list_moved_slots_between(
+    from: &str,
+    to: &str,
+    group_id: Option<i32>,
+) -> Result<Vec<(i32, String)>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT slot_account_id, slot_time FROM publish_job
+        WHERE slot_time >= ?1 AND slot_time <= ?2 AND (?3 IS NULL OR group_id = ?3)",
+    )?;
+    let slots = stmt
+        .query_map(rusqlite::params![from, to, group_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<(i32, String)>, _>>()?;
+    Ok(slots)
+}
+/// Jobs waiting for review, oldest first.
+pub fn list_pending_review() -> Result<PublishJobResponseData, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM publish_job
+        left join account on publish_job.account_id = account.id
+        WHERE publish_job.status = 5 ORDER BY publish_job.start_time ASC, publish_job.id ASC",
+        JOB_COLUMNS
+    ))?;
+    let data = stmt
+        .query_map([], map_row)?
+        .collect::<Result<Vec<PublishJobDetails>, _>>()?;
+    Ok(PublishJobResponseData { data })
+}
 pub fn list_all() -> Result<PublishJobResponseData, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare("
-    SELECT publish_job.id,publish_job.material, publish_job.account_id, publish_job.title, publish_job.status, 
-    publish_job.start_time,publish_job.end_time,account.device,publish_job.group_id,
-    publish_job.publish_type,publish_job.product_link,account.username,publish_job.remark
-    FROM publish_job
+    let mut stmt = conn.prepare(&format!(
+        "
+    SELECT {} FROM publish_job
     left join account on publish_job.account_id = account.id
     ORDER BY publish_job.id DESC LIMIT 2000
-    ")?;
+    ",
+        JOB_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let job_iter = stmt.query_map((), |row| {
-        Ok(PublishJobDetails {
-            id: row.get(0)?,
-            material: row.get(1)?,
-            account_id: row.get(2)?,
-            title: row.get(3)?,
-            status: row.get(4)?,
-            start_time: row.get(5)?,
-            end_time: row.get(6)?,
-            device: row.get(7)?,
-            group_id: row.get(8)?,
-            publish_type: row.get(9)?,
-            product_link: row.get(10)?,
-            username: row.get(11)?,
-            remark: row.get(12)?,
-        })
-    })?;
+    let job_iter = stmt.query_map((), map_row)?;
     for publish_job in job_iter {
         data.push(publish_job?);
     }
@@ -139,39 +403,33 @@ pub fn del(id: i32) -> Result<(), RunTimeError> {
     Ok(())
 }
 pub fn list_runable(agent_ip: String) -> Result<PublishJobResponseData, RunTimeError> {
+    //nothing is handed to agents while the scheduler is paused
+    if automation_pause::is_globally_paused() {
+        return Ok(PublishJobResponseData { data: Vec::new() });
+    }
+    let blocked_group_ids = automation_pause::blocked_group_ids()?;
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare("
-    SELECT publish_job.id,publish_job.material, publish_job.account_id, publish_job.title, 
-    publish_job.status, publish_job.start_time,publish_job.end_time,account.device,publish_job.group_id,
-    publish_job.publish_type,publish_job.product_link,account.username,publish_job.remark
-    FROM publish_job
+    let mut stmt = conn.prepare(&format!(
+        "
+    SELECT {} FROM publish_job
     left join account on publish_job.account_id = account.id
     left join device on account.device = device.serial
     WHERE publish_job.status < 2 AND device.agent_ip = ?1 
     AND publish_job.start_time < datetime('now', 'localtime') 
     AND device.online = 1
+    AND account.status = 'active'
     ORDER BY publish_job.id ASC
-    ")?;
+    ",
+        JOB_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let job_iter = stmt.query_map(rusqlite::params![agent_ip], |row| {
-        Ok(PublishJobDetails {
-            id: row.get(0)?,
-            material: row.get(1)?,
-            account_id: row.get(2)?,
-            title: row.get(3)?,
-            status: row.get(4)?,
-            start_time: row.get(5)?,
-            end_time: row.get(6)?,
-            device: row.get(7)?,
-            group_id: row.get(8)?,
-            publish_type: row.get(9)?,
-            product_link: row.get(10)?,
-            username: row.get(11)?,
-            remark: row.get(12)?,
-        })
-    })?;
+    let job_iter = stmt.query_map(rusqlite::params![agent_ip], map_row)?;
     for publish_job in job_iter {
-        data.push(publish_job?);
+        let publish_job = publish_job?;
+        if blocked_group_ids.contains(&publish_job.group_id) {
+            continue;
+        }
+        data.push(publish_job);
     }
     Ok(PublishJobResponseData { data })
 }
@@ -184,7 +442,8 @@ pub fn count_job_by_account_today(
     let mut stmt = conn.prepare(
         "
     SELECT count(*) FROM publish_job
-    WHERE account_id = ?1 AND start_time = ?2 AND DATE(create_time) = DATE('now')
+    WHERE ((account_id = ?1 AND start_time = ?2) OR (slot_account_id = ?1 AND slot_time = ?2))
+    AND DATE(create_time) = DATE('now')
     ",
     )?;
     let mut count = 0;
@@ -216,13 +475,76 @@ pub fn count_by_status() -> Result<Vec<CountGroupByStatus>, RunTimeError> {
     }
     Ok(data)
 }
-pub fn retry_all_failed() -> Result<(), RunTimeError> {
+//running jobs whose device went offline or that exceeded the group publish_timeout
+pub fn list_stuck() -> Result<Vec<StuckJobDetails>, RunTimeError> {
     let conn = database::get_conn()?;
-    conn.execute(
-        "UPDATE publish_job SET status = 0 WHERE status = 3",
+    let mut stmt = conn.prepare(
+        "
+    SELECT publish_job.id, account.device, device.online,
+    CAST(strftime('%s', datetime('now', 'localtime'))
+        - strftime('%s', COALESCE(publish_job.run_time, publish_job.start_time)) AS INTEGER) AS running_seconds
+    FROM publish_job
+    left join account on publish_job.account_id = account.id
+    left join device on account.device = device.serial
+    left join `group` on publish_job.group_id = `group`.id
+    WHERE publish_job.status = 1
+    AND (device.online = 0 OR running_seconds > COALESCE(`group`.publish_timeout, 1800))
+    ",
+    )?;
+    let job_iter = stmt.query_map(rusqlite::params![], |row| {
+        Ok(StuckJobDetails {
+            id: row.get(0)?,
+            device: row.get(1)?,
+            online: row.get(2)?,
+            running_seconds: row.get(3)?,
+        })
+    })?;
+    let mut data = Vec::new();
+    for job in job_iter {
+        data.push(job?);
+    }
+    Ok(data)
+}
+pub fn earliest_retry_time() -> Result<Option<String>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let retry_time = conn.query_row(
+        "SELECT MIN(next_retry_time) FROM publish_job WHERE status = 3",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(retry_time)
+}
+//re-queue failed jobs whose backoff delay has elapsed
+pub fn requeue_due_retries(conn: &Mutex<Connection>) -> Result<usize, RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    let count = conn.execute(
+        "UPDATE publish_job SET status = 0, next_retry_time = NULL
+        WHERE status = 3 AND next_retry_time IS NOT NULL
+        AND next_retry_time <= datetime('now', 'localtime')",
         rusqlite::params![],
     )?;
-    Ok(())
+    Ok(count)
+}
+pub fn retry_all_failed(
+    group_id: Option<i32>,
+    error_class: Option<String>,
+) -> Result<usize, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut query = "UPDATE publish_job SET status = 0, attempts = 0, next_retry_time = NULL
+    WHERE status IN (3, 4)"
+        .to_string();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(group_id) = group_id {
+        query.push_str(" AND group_id = ?");
+        params.push(group_id.into());
+    }
+    if let Some(error_class) = error_class {
+        query.push_str(" AND error_class = ?");
+        params.push(error_class.into());
+    }
+    let count = conn.execute(&query, rusqlite::params_from_iter(params))?;
+    Ok(count)
 }
 pub fn delete_all() -> Result<(), RunTimeError> {
     let conn = database::get_conn()?;
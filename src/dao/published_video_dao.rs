@@ -0,0 +1,48 @@
+use crate::models::PublishedVideoDetails;
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::{types::Value, Result};
+
+/// Posts the publish jobs produced, newest first, filtered by whichever
+/// of account, group and material is given.
+pub fn list(
+    account_id: Option<i32>,
+    group_id: Option<i32>,
+    material_id: Option<i32>,
+) -> Result<Vec<PublishedVideoDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut query =
+        "SELECT id, publish_job_id, account_id, username, group_id, material, material_id,
+        video_id, video_url, publish_time, create_time FROM published_video WHERE 1=1"
+            .to_string();
+    let mut params: Vec<Value> = Vec::new();
+    for (column, value) in [
+        ("account_id", account_id),
+        ("group_id", group_id),
+        ("material_id", material_id),
+    ] {
+        if let Some(value) = value {
+            query.push_str(&format!(" AND {} = ?", column));
+            params.push(value.into());
+        }
+    }
+    query.push_str(" ORDER BY id DESC LIMIT 2000");
+    let mut stmt = conn.prepare(&query)?;
+    let videos = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            Ok(PublishedVideoDetails {
+                id: row.get(0)?,
+                publish_job_id: row.get(1)?,
+                account_id: row.get(2)?,
+                username: row.get(3)?,
+                group_id: row.get(4)?,
+                material: row.get(5)?,
+                material_id: row.get(6)?,
+                video_id: row.get(7)?,
+                video_url: row.get(8)?,
+                publish_time: row.get(9)?,
+                create_time: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<PublishedVideoDetails>, _>>()?;
+    Ok(videos)
+}
@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use crate::models::{SchedulerRunData, SchedulerRunDetails};
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::{Connection, Result};
+
+pub fn save(conn: &Mutex<Connection>, data: &SchedulerRunData) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    let skips = serde_json::to_string(&data.skips)?;
+    conn.execute(
+        "INSERT INTO scheduler_run (trigger, group_id, duration_ms, publish_job_count, train_job_count, retry_count, skips)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            data.trigger,
+            data.group_id,
+            data.duration_ms,
+            data.publish_job_count,
+            data.train_job_count,
+            data.retry_count,
+            skips,
+        ],
+    )?;
+    Ok(())
+}
+pub fn list_recent() -> Result<Vec<SchedulerRunDetails>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, trigger, group_id, duration_ms, publish_job_count, train_job_count, retry_count,
+        skips, create_time FROM scheduler_run ORDER BY id DESC LIMIT 200",
+    )?;
+    let runs = stmt
+        .query_map([], |row| {
+            let skips: Option<String> = row.get(7)?;
+            Ok(SchedulerRunDetails {
+                id: row.get(0)?,
+                trigger: row.get(1)?,
+                group_id: row.get(2)?,
+                duration_ms: row.get(3)?,
+                publish_job_count: row.get(4)?,
+                train_job_count: row.get(5)?,
+                retry_count: row.get(6)?,
+                skips: skips
+                    .and_then(|skips| serde_json::from_str(&skips).ok())
+                    .unwrap_or_default(),
+                create_time: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<SchedulerRunDetails>, _>>()?;
+    Ok(runs)
+}
@@ -1,8 +1,10 @@
 use std::sync::Mutex;
 
-use crate::models::{CountGroupByStatus, TrainJobData, TrainJobDetails, TrainJobResponseData};
-use crate::{database, runtime_err::RunTimeError};
-use rusqlite::{Connection, Result};
+use crate::models::{
+    CountGroupByStatus, StuckJobDetails, TrainJobData, TrainJobDetails, TrainJobResponseData,
+};
+use crate::{automation_pause, database, retry_policy, runtime_err::RunTimeError};
+use rusqlite::{Connection, Result, Row};
 
 pub fn save(conn: &Mutex<Connection>, job_data: TrainJobData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
@@ -22,32 +24,47 @@ pub fn save(conn: &Mutex<Connection>, job_data: TrainJobData) -> Result<(), RunT
     )?;
     Ok(())
 }
+const JOB_COLUMNS: &str = "train_job.id,train_job.group_id,train_job.account_id,
+    train_job.like_probable,train_job.floow_probable,train_job.collect_probable,train_job.status,
+    train_job.start_time,train_job.end_time,account.device,account.username,train_job.duration,train_job.remark,
+    train_job.attempts,train_job.last_error,train_job.error_class,train_job.next_retry_time,
+    train_job.run_time";
+
+fn map_row(row: &Row) -> Result<TrainJobDetails> {
+    Ok(TrainJobDetails {
+        id: row.get(0)?,
+        group_id: row.get(1)?,
+        account_id: row.get(2)?,
+        like_probable: row.get(3)?,
+        floow_probable: row.get(4)?,
+        collect_probable: row.get(5)?,
+        status: row.get(6)?,
+        start_time: row.get(7)?,
+        end_time: row.get(8)?,
+        device: row.get(9)?,
+        username: row.get(10)?,
+        duration: row.get(11)?,
+        remark: row.get(12)?,
+        attempts: row.get(13)?,
+        last_error: row.get(14)?,
+        error_class: row.get(15)?,
+        next_retry_time: row.get(16)?,
+        run_time: row.get(17)?,
+    })
+}
 pub fn update(conn: &Mutex<Connection>, job_data: TrainJobData) -> Result<(), RunTimeError> {
     let _lock = conn.lock();
     let conn = database::get_conn()?;
     //get by id
-    let mut stmt = conn.prepare(
-        "select id,group_id, account_id, like_probable, floow_probable, collect_probable, 
-        status, start_time, end_time,duration,remark from train_job where id = ?1",
-    )?;
-    let mut job_iter = stmt.query_map(rusqlite::params![job_data.id.unwrap()], |row| {
-        Ok(TrainJobDetails {
-            id: row.get(0)?,
-            group_id: row.get(1)?,
-            account_id: row.get(2)?,
-            like_probable: row.get(3)?,
-            floow_probable: row.get(4)?,
-            collect_probable: row.get(5)?,
-            status: row.get(6)?,
-            start_time: row.get(7)?,
-            end_time: row.get(8)?,
-            device: None,
-            username: None,
-            duration: row.get(9)?,
-            remark: row.get(10)?,
-        })
-    })?;
-    let mut job = job_iter.next().unwrap().unwrap();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM train_job
+        left join account on train_job.account_id = account.id
+        WHERE train_job.id = ?1",
+        JOB_COLUMNS
+    ))?;
+    let mut job_iter = stmt.query_map(rusqlite::params![job_data.id.unwrap()], map_row)?;
+    let mut job = job_iter.next().ok_or(RunTimeError::NotFound)??;
+    let old_status = job.status;
     if job_data.group_id != None {
         job.group_id = job_data.group_id.unwrap();
     }
@@ -75,9 +92,37 @@ pub fn update(conn: &Mutex<Connection>, job_data: TrainJobData) -> Result<(), Ru
     if job_data.remark != None {
         job.remark = job_data.remark;
     }
+    //picked up by agent, remember when it started running
+    if job.status == 1 && old_status != 1 {
+        job.run_time = Some(
+            chrono::Local::now()
+                .naive_local()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        );
+    }
+    //failed by agent, apply the group retry policy
+    if job.status == 3 && old_status != 3 {
+        job.attempts += 1;
+        job.last_error = job_data.last_error.or(job.remark.clone());
+        job.error_class = job_data.error_class;
+        let policy = retry_policy::policy_for_group(job.group_id);
+        job.next_retry_time = policy.next_retry_time(job.attempts, job.error_class.as_deref());
+        if job.next_retry_time.is_none() {
+            log::warn!(
+                "train_job {} moved to dead letter after {} attempts, error_class: {:?}",
+                job.id,
+                job.attempts,
+                job.error_class
+            );
+            job.status = 4;
+        }
+    }
     conn.execute(
         "UPDATE train_job SET group_id = ?1, account_id = ?2, like_probable = ?3, 
-         floow_probable = ?4, collect_probable = ?5, status = ?6, start_time = ?7, duration = ?9, remark = ?10
+         floow_probable = ?4, collect_probable = ?5, status = ?6, start_time = ?7, duration = ?9, remark = ?10,
+         attempts = ?11, last_error = ?12, error_class = ?13, next_retry_time = ?14,
+         run_time = ?15
          WHERE id = ?8",
         rusqlite::params![
             job.group_id,
@@ -89,40 +134,28 @@ pub fn update(conn: &Mutex<Connection>, job_data: TrainJobData) -> Result<(), Ru
             job.start_time,
             job_data.id.unwrap(),
             job.duration,
-            job.remark
+            job.remark,
+            job.attempts,
+            job.last_error,
+            job.error_class,
+            job.next_retry_time,
+            job.run_time
         ],
     )?;
     Ok(())
 }
 pub fn list_all() -> Result<TrainJobResponseData, RunTimeError> {
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-    SELECT train_job.id,train_job.group_id,train_job.account_id,
-    train_job.like_probable,train_job.floow_probable,train_job.collect_probable,train_job.status,
-    train_job.start_time,train_job.end_time,account.device,account.username,train_job.duration,train_job.remark FROM train_job
+    SELECT {} FROM train_job
     left join account on train_job.account_id = account.id
     ORDER BY train_job.id DESC LIMIT 2000
     ",
-    )?;
+        JOB_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let job_iter = stmt.query_map((), |row| {
-        Ok(TrainJobDetails {
-            id: row.get(0)?,
-            group_id: row.get(1)?,
-            account_id: row.get(2)?,
-            like_probable: row.get(3)?,
-            floow_probable: row.get(4)?,
-            collect_probable: row.get(5)?,
-            status: row.get(6)?,
-            start_time: row.get(7)?,
-            end_time: row.get(8)?,
-            device: row.get(9)?,
-            username: row.get(10)?,
-            duration: row.get(11)?,
-            remark: row.get(12)?,
-        })
-    })?;
+    let job_iter = stmt.query_map((), map_row)?;
     for job in job_iter {
         data.push(job?);
     }
@@ -134,40 +167,33 @@ pub fn del(id: i32) -> Result<(), RunTimeError> {
     Ok(())
 }
 pub fn list_runable(agent_ip: String) -> Result<TrainJobResponseData, RunTimeError> {
+    //nothing is handed to agents while the scheduler is paused
+    if automation_pause::is_globally_paused() {
+        return Ok(TrainJobResponseData { data: Vec::new() });
+    }
+    let blocked_group_ids = automation_pause::blocked_group_ids()?;
     let conn = database::get_conn()?;
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-    SELECT train_job.id,train_job.group_id,train_job.account_id,
-    train_job.like_probable,train_job.floow_probable,train_job.collect_probable,train_job.status,
-    train_job.start_time,train_job.end_time,account.device,account.username,train_job.duration,train_job.remark FROM train_job
+    SELECT {} FROM train_job
     left join account on train_job.account_id = account.id
     left join device on account.device = device.serial
     WHERE train_job.status < 2 AND device.agent_ip = ?1 
     AND train_job.start_time < datetime('now', 'localtime') 
     AND device.online = 1
+    AND account.status = 'active'
     ORDER BY train_job.id ASC
     ",
-    )?;
+        JOB_COLUMNS
+    ))?;
     let mut data = Vec::new();
-    let job_iter = stmt.query_map(rusqlite::params![agent_ip], |row| {
-        Ok(TrainJobDetails {
-            id: row.get(0)?,
-            group_id: row.get(1)?,
-            account_id: row.get(2)?,
-            like_probable: row.get(3)?,
-            floow_probable: row.get(4)?,
-            collect_probable: row.get(5)?,
-            status: row.get(6)?,
-            start_time: row.get(7)?,
-            end_time: row.get(8)?,
-            device: row.get(9)?,
-            username: row.get(10)?,
-            duration: row.get(11)?,
-            remark: row.get(12)?,
-        })
-    })?;
+    let job_iter = stmt.query_map(rusqlite::params![agent_ip], map_row)?;
     for publish_job in job_iter {
-        data.push(publish_job?);
+        let publish_job = publish_job?;
+        if blocked_group_ids.contains(&publish_job.group_id) {
+            continue;
+        }
+        data.push(publish_job);
     }
     Ok(TrainJobResponseData { data })
 }
@@ -180,7 +206,8 @@ pub fn count_job_by_account_today(
     let mut stmt = conn.prepare(
         "
     SELECT count(*) FROM train_job
-    WHERE account_id = ?1 AND start_time = ?2 AND DATE(create_time) = DATE('now')
+    WHERE ((account_id = ?1 AND start_time = ?2) OR (slot_account_id = ?1 AND slot_time = ?2))
+    AND DATE(create_time) = DATE('now')
     ",
     )?;
     let mut count = 0;
@@ -213,13 +240,75 @@ pub fn count_by_status() -> Result<Vec<CountGroupByStatus>, RunTimeError> {
     }
     Ok(data)
 }
-pub fn retry_all_failed() -> Result<(), RunTimeError> {
+//running jobs whose device went offline or that exceeded their duration plus grace_seconds
+pub fn list_stuck(grace_seconds: i64) -> Result<Vec<StuckJobDetails>, RunTimeError> {
     let conn = database::get_conn()?;
-    conn.execute(
-        "UPDATE train_job SET status = 0 WHERE status = 3",
+    let mut stmt = conn.prepare(
+        "
+    SELECT train_job.id, account.device, device.online,
+    CAST(strftime('%s', datetime('now', 'localtime'))
+        - strftime('%s', COALESCE(train_job.run_time, train_job.start_time)) AS INTEGER) AS running_seconds
+    FROM train_job
+    left join account on train_job.account_id = account.id
+    left join device on account.device = device.serial
+    WHERE train_job.status = 1
+    AND (device.online = 0 OR running_seconds > train_job.duration + ?1)
+    ",
+    )?;
+    let job_iter = stmt.query_map(rusqlite::params![grace_seconds], |row| {
+        Ok(StuckJobDetails {
+            id: row.get(0)?,
+            device: row.get(1)?,
+            online: row.get(2)?,
+            running_seconds: row.get(3)?,
+        })
+    })?;
+    let mut data = Vec::new();
+    for job in job_iter {
+        data.push(job?);
+    }
+    Ok(data)
+}
+pub fn earliest_retry_time() -> Result<Option<String>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let retry_time = conn.query_row(
+        "SELECT MIN(next_retry_time) FROM train_job WHERE status = 3",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(retry_time)
+}
+//re-queue failed jobs whose backoff delay has elapsed
+pub fn requeue_due_retries(conn: &Mutex<Connection>) -> Result<usize, RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    let count = conn.execute(
+        "UPDATE train_job SET status = 0, next_retry_time = NULL
+        WHERE status = 3 AND next_retry_time IS NOT NULL
+        AND next_retry_time <= datetime('now', 'localtime')",
         rusqlite::params![],
     )?;
-    Ok(())
+    Ok(count)
+}
+pub fn retry_all_failed(
+    group_id: Option<i32>,
+    error_class: Option<String>,
+) -> Result<usize, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut query = "UPDATE train_job SET status = 0, attempts = 0, next_retry_time = NULL
+    WHERE status IN (3, 4)"
+        .to_string();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(group_id) = group_id {
+        query.push_str(" AND group_id = ?");
+        params.push(group_id.into());
+    }
+    if let Some(error_class) = error_class {
+        query.push_str(" AND error_class = ?");
+        params.push(error_class.into());
+    }
+    let count = conn.execute(&query, rusqlite::params_from_iter(params))?;
+    Ok(count)
 }
 pub fn delete_all() -> Result<(), RunTimeError> {
     let conn = database::get_conn()?;
@@ -0,0 +1,94 @@
+use std::sync::Mutex;
+
+use crate::models::{UploadSessionData, UploadSessionDetails};
+use crate::{database, runtime_err::RunTimeError};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
+
+const SESSION_COLUMNS: &str =
+    "id, file_name, size, received, group_id, dedup, sha256, create_time, update_time";
+
+fn map_row(row: &Row) -> Result<UploadSessionDetails> {
+    Ok(UploadSessionDetails {
+        id: row.get(0)?,
+        file_name: row.get(1)?,
+        size: row.get(2)?,
+        received: row.get(3)?,
+        group_id: row.get(4)?,
+        dedup: row.get(5)?,
+        sha256: row.get(6)?,
+        create_time: row.get(7)?,
+        update_time: row.get(8)?,
+    })
+}
+
+pub fn save(
+    conn: &Mutex<Connection>,
+    id: &str,
+    data: &UploadSessionData,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    conn.execute(
+        "INSERT INTO upload_session (id, file_name, size, group_id, dedup, sha256)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            id,
+            data.file_name,
+            data.size,
+            data.group_id.unwrap_or(0),
+            data.dedup,
+            data.sha256,
+        ],
+    )?;
+    Ok(())
+}
+pub fn get_by_id(id: &str) -> Result<UploadSessionDetails, RunTimeError> {
+    let conn = database::get_conn()?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM upload_session WHERE id = ?1",
+            SESSION_COLUMNS
+        ),
+        rusqlite::params![id],
+        map_row,
+    )
+    .optional()?
+    .ok_or(RunTimeError::NotFound)
+}
+pub fn update_received(
+    conn: &Mutex<Connection>,
+    id: &str,
+    received: i64,
+) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    conn.execute(
+        "UPDATE upload_session SET received = ?1, update_time = datetime('now','localtime')
+        WHERE id = ?2",
+        rusqlite::params![received, id],
+    )?;
+    Ok(())
+}
+pub fn del(conn: &Mutex<Connection>, id: &str) -> Result<(), RunTimeError> {
+    let _lock = conn.lock();
+    let conn = database::get_conn()?;
+    conn.execute(
+        "DELETE FROM upload_session WHERE id = ?1",
+        rusqlite::params![id],
+    )?;
+    Ok(())
+}
+/// Sessions without a chunk for `hours`, the client gave up on them.
+pub fn list_expired(hours: i64) -> Result<Vec<String>, RunTimeError> {
+    let conn = database::get_conn()?;
+    let mut stmt = conn.prepare(
+        "SELECT id FROM upload_session
+        WHERE update_time < datetime('now','localtime', '-' || ?1 || ' hours')",
+    )?;
+    let mut data = Vec::new();
+    let id_iter = stmt.query_map(rusqlite::params![hours], |row| row.get(0))?;
+    for id in id_iter {
+        data.push(id?);
+    }
+    Ok(data)
+}
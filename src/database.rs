@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use rusqlite::{Connection, Result};
 
 use crate::runtime_err::RunTimeError;
@@ -90,6 +92,101 @@ pub fn create_databases() -> Result<(), RunTimeError> {
         "topic",
         "ALTER TABLE `group` ADD COLUMN topic TEXT DEFAULT NULL",
     )?;
+    add_column(
+        "group",
+        "max_attempts",
+        "ALTER TABLE `group` ADD COLUMN max_attempts INTEGER NOT NULL DEFAULT 3",
+    )?;
+    add_column(
+        "group",
+        "retry_delay",
+        "ALTER TABLE `group` ADD COLUMN retry_delay INTEGER NOT NULL DEFAULT 60",
+    )?;
+    add_column(
+        "group",
+        "retry_error_classes",
+        "ALTER TABLE `group` ADD COLUMN retry_error_classes TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "publish_timeout",
+        "ALTER TABLE `group` ADD COLUMN publish_timeout INTEGER NOT NULL DEFAULT 1800",
+    )?;
+    add_column(
+        "group",
+        "paused",
+        "ALTER TABLE `group` ADD COLUMN paused INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column(
+        "group",
+        "resume_time",
+        "ALTER TABLE `group` ADD COLUMN resume_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "maintenance_windows",
+        "ALTER TABLE `group` ADD COLUMN maintenance_windows TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "blackout_dates",
+        "ALTER TABLE `group` ADD COLUMN blackout_dates TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "min_duration",
+        "ALTER TABLE `group` ADD COLUMN min_duration REAL DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "max_duration",
+        "ALTER TABLE `group` ADD COLUMN max_duration REAL DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "aspect_ratio",
+        "ALTER TABLE `group` ADD COLUMN aspect_ratio TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "selection_strategy",
+        "ALTER TABLE `group` ADD COLUMN selection_strategy TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "tag_weights",
+        "ALTER TABLE `group` ADD COLUMN tag_weights TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "reuse_after_days",
+        "ALTER TABLE `group` ADD COLUMN reuse_after_days INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "inventory_alert_days",
+        "ALTER TABLE `group` ADD COLUMN inventory_alert_days INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "requires_approval",
+        "ALTER TABLE `group` ADD COLUMN requires_approval INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "max_posts_per_day",
+        "ALTER TABLE `group` ADD COLUMN max_posts_per_day INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "max_posts_per_week",
+        "ALTER TABLE `group` ADD COLUMN max_posts_per_week INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "group",
+        "min_post_spacing",
+        "ALTER TABLE `group` ADD COLUMN min_post_spacing INTEGER DEFAULT NULL",
+    )?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS device (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -116,6 +213,52 @@ pub fn create_databases() -> Result<(), RunTimeError> {
           );",
         (),
     )?;
+    add_column(
+        "account",
+        "max_posts_per_day",
+        "ALTER TABLE account ADD COLUMN max_posts_per_day INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "account",
+        "max_posts_per_week",
+        "ALTER TABLE account ADD COLUMN max_posts_per_week INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "account",
+        "min_post_spacing",
+        "ALTER TABLE account ADD COLUMN min_post_spacing INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "account",
+        "status",
+        "ALTER TABLE account ADD COLUMN status TEXT NOT NULL DEFAULT 'active'",
+    )?;
+    add_column(
+        "account",
+        "status_reason",
+        "ALTER TABLE account ADD COLUMN status_reason TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "account",
+        "status_time",
+        "ALTER TABLE account ADD COLUMN status_time TEXT DEFAULT NULL",
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS account_status_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        account_id INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        previous_status TEXT NOT NULL,
+        reason TEXT DEFAULT NULL,
+        source TEXT NOT NULL,
+        create_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_account_status_history_account ON account_status_history (account_id)",
+        (),
+    )?;
 
     conn.execute(
         "CREATE TABLE IF NOT EXISTS material (
@@ -158,6 +301,77 @@ pub fn create_databases() -> Result<(), RunTimeError> {
         "remark",
         "ALTER TABLE `publish_job` ADD COLUMN remark TEXT DEFAULT NULL",
     )?;
+    add_column(
+        "publish_job",
+        "attempts",
+        "ALTER TABLE `publish_job` ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column(
+        "publish_job",
+        "last_error",
+        "ALTER TABLE `publish_job` ADD COLUMN last_error TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "error_class",
+        "ALTER TABLE `publish_job` ADD COLUMN error_class TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "next_retry_time",
+        "ALTER TABLE `publish_job` ADD COLUMN next_retry_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "run_time",
+        "ALTER TABLE `publish_job` ADD COLUMN run_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "video_id",
+        "ALTER TABLE `publish_job` ADD COLUMN video_id TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "video_url",
+        "ALTER TABLE `publish_job` ADD COLUMN video_url TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "publish_time",
+        "ALTER TABLE `publish_job` ADD COLUMN publish_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "platform_message",
+        "ALTER TABLE `publish_job` ADD COLUMN platform_message TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "reviewer",
+        "ALTER TABLE `publish_job` ADD COLUMN reviewer TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "review_comment",
+        "ALTER TABLE `publish_job` ADD COLUMN review_comment TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "review_time",
+        "ALTER TABLE `publish_job` ADD COLUMN review_time TEXT DEFAULT NULL",
+    )?;
+    //the slot a job was planned for before a bulk reschedule or reassign moved it
+    add_column(
+        "publish_job",
+        "slot_account_id",
+        "ALTER TABLE `publish_job` ADD COLUMN slot_account_id INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "publish_job",
+        "slot_time",
+        "ALTER TABLE `publish_job` ADD COLUMN slot_time TEXT DEFAULT NULL",
+    )?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS train_job (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -185,6 +399,114 @@ pub fn create_databases() -> Result<(), RunTimeError> {
         "remark",
         "ALTER TABLE `train_job` ADD COLUMN remark TEXT DEFAULT NULL",
     )?;
+    add_column(
+        "train_job",
+        "attempts",
+        "ALTER TABLE `train_job` ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column(
+        "train_job",
+        "last_error",
+        "ALTER TABLE `train_job` ADD COLUMN last_error TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "train_job",
+        "error_class",
+        "ALTER TABLE `train_job` ADD COLUMN error_class TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "train_job",
+        "next_retry_time",
+        "ALTER TABLE `train_job` ADD COLUMN next_retry_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "train_job",
+        "run_time",
+        "ALTER TABLE `train_job` ADD COLUMN run_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "train_job",
+        "slot_account_id",
+        "ALTER TABLE `train_job` ADD COLUMN slot_account_id INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "train_job",
+        "slot_time",
+        "ALTER TABLE `train_job` ADD COLUMN slot_time TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "sha256",
+        "ALTER TABLE `material` ADD COLUMN sha256 TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "duration",
+        "ALTER TABLE `material` ADD COLUMN duration REAL DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "width",
+        "ALTER TABLE `material` ADD COLUMN width INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "height",
+        "ALTER TABLE `material` ADD COLUMN height INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "codec",
+        "ALTER TABLE `material` ADD COLUMN codec TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "bitrate",
+        "ALTER TABLE `material` ADD COLUMN bitrate INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "file_size",
+        "ALTER TABLE `material` ADD COLUMN file_size INTEGER DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "tags",
+        "ALTER TABLE `material` ADD COLUMN tags TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "collection",
+        "ALTER TABLE `material` ADD COLUMN collection TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "caption",
+        "ALTER TABLE `material` ADD COLUMN caption TEXT DEFAULT NULL",
+    )?;
+    add_column(
+        "material",
+        "hashtags",
+        "ALTER TABLE `material` ADD COLUMN hashtags TEXT DEFAULT NULL",
+    )?;
+    //the index can only be built once duplicates uploaded before it existed are
+    //cleaned up, GET /api/material/duplicates lists them
+    let duplicates: i32 = conn.query_row(
+        "SELECT count(*) FROM (SELECT 1 FROM material GROUP BY group_id, md5 HAVING count(*) > 1)",
+        (),
+        |row| row.get(0),
+    )?;
+    if duplicates == 0 {
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_material_group_md5 ON material (group_id, md5)",
+            (),
+        )?;
+    } else {
+        log::warn!(
+            "{} duplicate material hashes, unique index idx_material_group_md5 not created",
+            duplicates
+        );
+    }
 
     conn.execute(
         "CREATE TABLE IF NOT EXISTS dialog_watcher (
@@ -241,6 +563,142 @@ pub fn create_databases() -> Result<(), RunTimeError> {
       );",
         (),
     )?;
+    //blob, content addressed material files shared by material rows
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS blob (
+        sha256 TEXT PRIMARY KEY,
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL DEFAULT 0,
+        ref_count INTEGER NOT NULL DEFAULT 0,
+        create_time TEXT DEFAULT CURRENT_TIMESTAMP
+      );",
+        (),
+    )?;
+    //materials hashed before the blob table existed keep their original file
+    conn.execute(
+        "INSERT OR IGNORE INTO blob (sha256, path, ref_count)
+        SELECT sha256, min(name), count(*) FROM material WHERE sha256 IS NOT NULL GROUP BY sha256",
+        (),
+    )?;
+    //upload_session, resumable uploads, `received` bytes are in tmp/<id>.part
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS upload_session (
+        id TEXT PRIMARY KEY,
+        file_name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        received INTEGER NOT NULL DEFAULT 0,
+        group_id INTEGER NOT NULL DEFAULT 0,
+        dedup TEXT DEFAULT NULL,
+        sha256 TEXT DEFAULT NULL,
+        create_time TEXT DEFAULT (datetime('now','localtime')),
+        update_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    //import_job, bulk material imports, an uploaded archive is kept in tmp/ until done
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_job (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        source_type TEXT NOT NULL,
+        source TEXT NOT NULL,
+        group_id INTEGER NOT NULL DEFAULT 0,
+        map_subfolders INTEGER NOT NULL DEFAULT 0,
+        dedup TEXT NOT NULL DEFAULT 'skip',
+        status INTEGER NOT NULL DEFAULT 0,
+        total INTEGER NOT NULL DEFAULT 0,
+        processed INTEGER NOT NULL DEFAULT 0,
+        created INTEGER NOT NULL DEFAULT 0,
+        duplicates INTEGER NOT NULL DEFAULT 0,
+        failed INTEGER NOT NULL DEFAULT 0,
+        error TEXT DEFAULT NULL,
+        create_time TEXT DEFAULT (datetime('now','localtime')),
+        update_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_file (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        group_id INTEGER NOT NULL DEFAULT 0,
+        status TEXT NOT NULL,
+        material_id INTEGER DEFAULT NULL,
+        message TEXT DEFAULT NULL
+      );",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_import_file_job ON import_file (job_id)",
+        (),
+    )?;
+    //publish_history, which account got which material, drives material reuse
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS publish_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        account_id INTEGER NOT NULL,
+        material_id INTEGER NOT NULL,
+        group_id INTEGER NOT NULL DEFAULT 0,
+        create_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_publish_history_material ON publish_history (material_id, account_id)",
+        (),
+    )?;
+    //published_video, the post a publish job produced, joined by analytics on username
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS published_video (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        publish_job_id INTEGER NOT NULL UNIQUE,
+        account_id INTEGER NOT NULL,
+        username TEXT DEFAULT NULL,
+        group_id INTEGER NOT NULL DEFAULT 0,
+        material TEXT NOT NULL,
+        material_id INTEGER DEFAULT NULL,
+        video_id TEXT DEFAULT NULL,
+        video_url TEXT DEFAULT NULL,
+        publish_time TEXT NOT NULL,
+        create_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_published_video_username ON published_video (username, publish_time)",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_published_video_material ON published_video (material_id)",
+        (),
+    )?;
+    //notification, raised by the scheduler for things that need attention
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notification (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        group_id INTEGER DEFAULT NULL,
+        message TEXT NOT NULL,
+        read INTEGER NOT NULL DEFAULT 0,
+        create_time TEXT DEFAULT (datetime('now','localtime'))
+      );",
+        (),
+    )?;
+    //scheduler_run
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scheduler_run (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        trigger TEXT NOT NULL,
+        group_id INTEGER DEFAULT NULL,
+        duration_ms INTEGER NOT NULL DEFAULT 0,
+        publish_job_count INTEGER NOT NULL DEFAULT 0,
+        train_job_count INTEGER NOT NULL DEFAULT 0,
+        retry_count INTEGER NOT NULL DEFAULT 0,
+        skips TEXT DEFAULT NULL,
+        create_time TEXT DEFAULT CURRENT_TIMESTAMP
+      );",
+        (),
+    )?;
     // data_analytics
     conn.execute(
         "CREATE TABLE IF NOT EXISTS data_analytics (
@@ -258,6 +716,151 @@ pub fn create_databases() -> Result<(), RunTimeError> {
       );",
         (),
     )?;
+    //unique accounts and devices, rows that clash from before are merged first
+    let indexed: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'index' AND name = 'idx_account_email'",
+        (),
+        |row| row.get(0),
+    )?;
+    if !indexed {
+        merge_duplicates()?;
+    }
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_account_email ON account (email COLLATE NOCASE)
+        WHERE email != ''",
+        (),
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_account_username ON account (username COLLATE NOCASE)
+        WHERE username != ''",
+        (),
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_device_serial ON device (serial)",
+        (),
+    )?;
 
     Ok(())
 }
+/// Merges accounts that share an email or username into the oldest one and
+/// keeps the latest row of a device serial, then notifies what it merged.
+fn merge_duplicates() -> Result<(), RunTimeError> {
+    let mut conn = get_conn()?;
+    let tx = conn.transaction()?;
+    let mut accounts = Vec::new();
+    {
+        let mut stmt = tx.prepare("SELECT id, email, username FROM account ORDER BY id")?;
+        let rows = stmt.query_map((), |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        for row in rows {
+            accounts.push(row?);
+        }
+    }
+    //email or username key to the account that keeps it
+    let mut owners: HashMap<String, i32> = HashMap::new();
+    let mut report = Vec::new();
+    for (id, email, username) in accounts {
+        let username = username.unwrap_or_default();
+        let keys: Vec<String> = [("email", email.trim()), ("username", username.trim())]
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(field, value)| format!("{}:{}", field, value.to_lowercase()))
+            .collect();
+        let keeper = keys.iter().find_map(|key| owners.get(key).copied());
+        for key in keys {
+            owners.entry(key).or_insert(keeper.unwrap_or(id));
+        }
+        let Some(keeper) = keeper else {
+            continue;
+        };
+        for table in [
+            "publish_job",
+            "train_job",
+            "publish_history",
+            "published_video",
+            "post_comment_topic_comment",
+            "account_status_history",
+        ] {
+            tx.execute(
+                &format!("UPDATE {} SET account_id = ?1 WHERE account_id = ?2", table),
+                rusqlite::params![keeper, id],
+            )?;
+        }
+        //slots moved jobs were originally planned for
+        for table in ["publish_job", "train_job"] {
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET slot_account_id = ?1 WHERE slot_account_id = ?2",
+                    table
+                ),
+                rusqlite::params![keeper, id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE account SET
+            pwd = CASE WHEN pwd = '' THEN (SELECT pwd FROM account WHERE id = ?2) ELSE pwd END,
+            device = COALESCE(NULLIF(device, ''), (SELECT device FROM account WHERE id = ?2)),
+            group_id = COALESCE(NULLIF(group_id, 0), (SELECT group_id FROM account WHERE id = ?2))
+            WHERE id = ?1",
+            rusqlite::params![keeper, id],
+        )?;
+        tx.execute("DELETE FROM account WHERE id = ?1", rusqlite::params![id])?;
+        //the keeper takes over an email or username it lacks when nobody else has it
+        tx.execute(
+            "UPDATE account SET email = ?2 WHERE id = ?1 AND email = '' AND ?2 != ''
+            AND NOT EXISTS (SELECT 1 FROM account WHERE email = ?2 COLLATE NOCASE)",
+            rusqlite::params![keeper, email.trim()],
+        )?;
+        tx.execute(
+            "UPDATE account SET username = ?2 WHERE id = ?1 AND COALESCE(username, '') = ''
+            AND ?2 != '' AND NOT EXISTS (SELECT 1 FROM account WHERE username = ?2 COLLATE NOCASE)",
+            rusqlite::params![keeper, username.trim()],
+        )?;
+        report.push(format!(
+            "account {} ({} {}) merged into account {}",
+            id, email, username, keeper
+        ));
+    }
+    let mut serials = Vec::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT serial, COUNT(*) FROM device GROUP BY serial HAVING COUNT(*) > 1 ORDER BY serial",
+        )?;
+        let rows = stmt.query_map((), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?;
+        for row in rows {
+            serials.push(row?);
+        }
+    }
+    for (serial, count) in serials {
+        tx.execute(
+            "DELETE FROM device WHERE serial = ?1
+            AND id != (SELECT MAX(id) FROM device WHERE serial = ?1)",
+            rusqlite::params![serial],
+        )?;
+        report.push(format!(
+            "{} rows of device {} merged into one",
+            count, serial
+        ));
+    }
+    if !report.is_empty() {
+        for line in &report {
+            log::warn!("{}", line);
+        }
+        tx.execute(
+            "INSERT INTO notification (kind, message) VALUES ('duplicates_merged', ?1)",
+            rusqlite::params![format!(
+                "merged duplicates before adding unique indexes: {}",
+                report.join("; ")
+            )],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+
+use crate::{
+    dao::{publish_job_dao, train_job_dao},
+    models::{PublishJobData, StuckJobDetails, TrainJobData},
+};
+
+//extra time a train job may run past its duration before it is reaped
+const TRAIN_TIMEOUT_GRACE: i64 = 600;
+
+fn stuck_reason(job: &StuckJobDetails) -> (String, String) {
+    if job.online == Some(0) {
+        (
+            format!(
+                "reaped: device {} went offline while running",
+                job.device.clone().unwrap_or_default()
+            ),
+            "device_offline".to_string(),
+        )
+    } else {
+        (
+            format!("reaped: running for {} seconds", job.running_seconds),
+            "timeout".to_string(),
+        )
+    }
+}
+
+/// Fails running jobs whose device went offline or that ran past their
+/// expected duration, the retry policy of the group decides whether they
+/// are re-queued or moved to the dead letter state. Returns the number of reaped jobs.
+pub fn reap(conn: &Mutex<Connection>) -> usize {
+    let mut count = 0;
+    match publish_job_dao::list_stuck() {
+        Ok(jobs) => {
+            for job in jobs {
+                let (remark, error_class) = stuck_reason(&job);
+                log::warn!("publish_job {} {}", job.id, remark);
+                let result = publish_job_dao::update(
+                    conn,
+                    PublishJobData {
+                        id: Some(job.id),
+                        material: None,
+                        account_id: None,
+                        title: None,
+                        status: Some(3),
+                        start_time: None,
+                        group_id: None,
+                        publish_type: 0,
+                        product_link: None,
+                        remark: Some(remark),
+                        last_error: None,
+                        error_class: Some(error_class),
+                    },
+                );
+                match result {
+                    Ok(_) => count += 1,
+                    Err(err) => log::error!("reap publish_job {} err -> {:?}", job.id, err),
+                }
+            }
+        }
+        Err(err) => log::error!("publish_job_dao::list_stuck err -> {:?}", err),
+    }
+    match train_job_dao::list_stuck(TRAIN_TIMEOUT_GRACE) {
+        Ok(jobs) => {
+            for job in jobs {
+                let (remark, error_class) = stuck_reason(&job);
+                log::warn!("train_job {} {}", job.id, remark);
+                let result = train_job_dao::update(
+                    conn,
+                    TrainJobData {
+                        id: Some(job.id),
+                        group_id: None,
+                        floow_probable: None,
+                        like_probable: None,
+                        collect_probable: None,
+                        account_id: None,
+                        status: Some(3),
+                        start_time: None,
+                        duration: None,
+                        remark: Some(remark),
+                        last_error: None,
+                        error_class: Some(error_class),
+                    },
+                );
+                match result {
+                    Ok(_) => count += 1,
+                    Err(err) => log::error!("reap train_job {} err -> {:?}", job.id, err),
+                }
+            }
+        }
+        Err(err) => log::error!("train_job_dao::list_stuck err -> {:?}", err),
+    }
+    count
+}
@@ -1,45 +1,210 @@
 use actix::prelude::*;
 use actix_web::web;
-use rand::{seq::SliceRandom, thread_rng};
 use rusqlite::Connection;
 
-use std::{sync::Mutex, time::Duration};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
 
 use crate::{
+    automation_pause, caption_template,
     dao::account_dao,
     dao::group_dao,
     dao::material_dao,
     dao::publish_job_dao,
+    dao::scheduler_run_dao,
     dao::train_job_dao,
-    models::{PublishJobData, TrainJobData},
+    material_inventory, material_selection,
+    models::{
+        AccountDetails, AccountStatus, GroupDetails, PublishJobData, PublishType,
+        SchedulePreviewItem, SchedulerRunData, TrainJobData,
+    },
+    publish_quota,
+    runtime_err::RunTimeError,
 };
 
+//longest time the scheduler sleeps when nothing is due
+const MAX_IDLE: Duration = Duration::from_secs(3600);
+//changes made through the api within this delay are handled by one pass
+const WAKE_DEBOUNCE: Duration = Duration::from_secs(2);
+
 pub struct JobScheduActor {
     pub conn: web::Data<Mutex<Connection>>,
+    next_pass: Option<SpawnHandle>,
+    wake_pass: Option<SpawnHandle>,
+    wake_group_ids: Vec<Option<i32>>,
 }
 impl Actor for JobScheduActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        self.schedule_check(ctx);
+        let _ = self.run_pass(None, "startup");
+        self.schedule_next(ctx);
+    }
+}
+
+/// Runs a scheduler pass right away, restricted to one group when `group_id` is set.
+pub struct RunNow {
+    pub group_id: Option<i32>,
+}
+impl Message for RunNow {
+    type Result = Result<SchedulerRunData, RunTimeError>;
+}
+impl Handler<RunNow> for JobScheduActor {
+    type Result = Result<SchedulerRunData, RunTimeError>;
+
+    fn handle(&mut self, msg: RunNow, ctx: &mut Context<Self>) -> Self::Result {
+        let run = self.run_pass(msg.group_id, "run_now");
+        self.schedule_next(ctx);
+        run
+    }
+}
+
+/// Sent by the api when a group, account or material changed.
+pub struct Wake {
+    pub group_id: Option<i32>,
+}
+impl Message for Wake {
+    type Result = ();
+}
+impl Handler<Wake> for JobScheduActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Wake, ctx: &mut Context<Self>) -> Self::Result {
+        self.wake_group_ids.push(msg.group_id);
+        if self.wake_pass.is_some() {
+            return;
+        }
+        self.wake_pass = Some(ctx.run_later(WAKE_DEBOUNCE, |act, ctx| {
+            act.wake_pass = None;
+            let mut group_ids = std::mem::take(&mut act.wake_group_ids);
+            if group_ids.contains(&None) {
+                let _ = act.run_pass(None, "change");
+            } else {
+                group_ids.sort();
+                group_ids.dedup();
+                for group_id in group_ids {
+                    let _ = act.run_pass(group_id, "change");
+                }
+            }
+            act.schedule_next(ctx);
+        }));
+    }
+}
+
+/// Sent when a job failed, so a retry due earlier than the planned pass is not missed.
+pub struct Reschedule;
+impl Message for Reschedule {
+    type Result = ();
+}
+impl Handler<Reschedule> for JobScheduActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Reschedule, ctx: &mut Context<Self>) -> Self::Result {
+        self.schedule_next(ctx);
+    }
+}
+
+#[derive(Default)]
+struct PassStats {
+    publish_jobs: i32,
+    train_jobs: i32,
+    retries: i32,
+    skips: BTreeMap<String, i32>,
+    //group id -> slots left empty because the group ran out of material
+    starved_slots: BTreeMap<i32, i32>,
+}
+impl PassStats {
+    fn skip(&mut self, reason: &str) {
+        *self.skips.entry(reason.to_string()).or_insert(0) += 1;
     }
 }
 
 impl JobScheduActor {
-    fn schedule_check(&self, ctx: &mut <Self as Actor>::Context) {
-        ctx.run_later(Duration::from_secs(60), |act: &mut JobScheduActor, ctx| {
-            act.check_publish_job();
-            act.check_train_job();
-            act.schedule_check(ctx);
-        });
-    }
-    fn check_train_job(&self) {
+    pub fn new(conn: web::Data<Mutex<Connection>>) -> JobScheduActor {
+        JobScheduActor {
+            conn,
+            next_pass: None,
+            wake_pass: None,
+            wake_group_ids: Vec::new(),
+        }
+    }
+    //sleep until the next time something becomes due
+    fn schedule_next(&mut self, ctx: &mut <Self as Actor>::Context) {
+        if let Some(handle) = self.next_pass.take() {
+            ctx.cancel_future(handle);
+        }
+        let now = chrono::Local::now().naive_local();
+        let delay = match next_due_time(now) {
+            Ok(due) => (due - now).to_std().unwrap_or(Duration::ZERO).min(MAX_IDLE),
+            Err(err) => {
+                log::warn!("next_due_time err -> {:?}", err);
+                MAX_IDLE
+            }
+        };
+        log::debug!("next scheduler pass in {:?}", delay);
+        self.next_pass = Some(ctx.run_later(delay, |act, ctx| {
+            act.next_pass = None;
+            let _ = act.run_pass(None, "timer");
+            act.schedule_next(ctx);
+        }));
+    }
+    fn run_pass(
+        &self,
+        group_id: Option<i32>,
+        trigger: &str,
+    ) -> Result<SchedulerRunData, RunTimeError> {
+        let started = std::time::Instant::now();
+        let mut stats = PassStats::default();
+        self.check_retry_job(&mut stats);
+        self.check_publish_job(group_id, &mut stats);
+        self.check_inventory(group_id, &stats);
+        self.check_train_job(group_id, &mut stats);
+        let run = SchedulerRunData {
+            trigger: trigger.to_string(),
+            group_id,
+            duration_ms: started.elapsed().as_millis() as i64,
+            publish_job_count: stats.publish_jobs,
+            train_job_count: stats.train_jobs,
+            retry_count: stats.retries,
+            skips: stats.skips,
+        };
+        log::info!("scheduler pass -> {:?}", run);
+        let result = scheduler_run_dao::save(&self.conn, &run);
+        if let Err(err) = &result {
+            log::warn!("scheduler_run_dao::save err -> {:?}", err);
+        }
+        result.map(|_| run)
+    }
+    fn check_retry_job(&self, stats: &mut PassStats) {
+        //re-queue failed jobs whose retry backoff has elapsed
+        match publish_job_dao::requeue_due_retries(&self.conn) {
+            Ok(count) if count > 0 => {
+                log::info!("requeue {} failed publish_job", count);
+                stats.retries += count as i32;
+            }
+            Ok(_) => {}
+            Err(err) => log::warn!("publish_job_dao::requeue_due_retries err -> {:?}", err),
+        }
+        match train_job_dao::requeue_due_retries(&self.conn) {
+            Ok(count) if count > 0 => {
+                log::info!("requeue {} failed train_job", count);
+                stats.retries += count as i32;
+            }
+            Ok(_) => {}
+            Err(err) => log::warn!("train_job_dao::requeue_due_retries err -> {:?}", err),
+        }
+    }
+    fn check_train_job(&self, group_id: Option<i32>, stats: &mut PassStats) {
         //list all auto train group
         let result = group_dao::list_all_auto_train();
         if let Ok(data) = result {
             for group in data.data {
                 //check open auto train
-                if group.auto_train != 1 {
+                if group.auto_train != 1 || group_id.is_some_and(|id| id != group.id) {
                     continue;
                 }
                 //get account in group
@@ -47,44 +212,27 @@ impl JobScheduActor {
                 if let Ok(account_data) = result {
                     //check has unfinish train_job by account
                     for account in account_data.data {
-                        let start_times = group.train_start_time.split(",");
-                        for start_time in start_times {
-                            let today = chrono::Local::now()
-                                .naive_local()
-                                .format("%Y-%m-%d")
-                                .to_string();
-                            let start_time = format!("{} {}:00", today, start_time);
-                            //if start_time < now, continue
-                            if chrono::NaiveDateTime::parse_from_str(
-                                &start_time,
-                                "%Y-%m-%d %H:%M:%S",
-                            )
-                            .unwrap()
-                                < chrono::Local::now().naive_local()
-                            {
+                        let today = chrono::Local::now().naive_local().date();
+                        for start_time in upcoming_slots(&group.train_start_time, today) {
+                            if let Some(reason) = account_skip_reason(&account) {
+                                log::info!("account {} skipped: {}", account.id, reason);
+                                stats.skip(reason);
                                 continue;
                             }
-                            let account = account.clone();
-                            let uesrname = account.username;
-                            let email = account.email;
-                            let id = account.id;
-                            if uesrname.is_none() {
-                                log::warn!("account.username is none");
-                                continue;
-                            }
-                            let username = uesrname.unwrap();
-                            if username.is_empty() {
-                                log::warn!("account.username is empty");
-                                continue;
-                            }
-                            //check username is email
-                            if username.is_empty() || username == email {
-                                log::info!("username is empty or email,can't use it to publish");
+                            if let Some(reason) = slot_block_reason(&group, &start_time) {
+                                log::info!(
+                                    "group {} slot {} skipped: {}",
+                                    group.id,
+                                    start_time,
+                                    reason
+                                );
+                                stats.skip(&reason);
                                 continue;
                             }
+                            let id = account.id;
                             let result: Result<i32, crate::runtime_err::RunTimeError> =
                                 train_job_dao::count_job_by_account_today(
-                                    id.clone(),
+                                    id,
                                     start_time.to_owned(),
                                 );
                             if let Ok(count) = result {
@@ -94,7 +242,7 @@ impl JobScheduActor {
                                     let job_data = TrainJobData {
                                         id: None,
                                         group_id: Some(group_clone.id),
-                                        account_id: Some(id.clone()),
+                                        account_id: Some(id),
                                         floow_probable: Some(group_clone.floow_probable),
                                         like_probable: Some(group_clone.like_probable),
                                         collect_probable: Some(group_clone.collect_probable),
@@ -102,6 +250,8 @@ impl JobScheduActor {
                                         start_time: Some(start_time.to_owned()),
                                         duration: Some(group_clone.train_duration),
                                         remark: None,
+                                        last_error: None,
+                                        error_class: None,
                                     };
                                     let job_data_clone = job_data.clone();
                                     let result = train_job_dao::save(&self.conn, job_data_clone);
@@ -114,6 +264,7 @@ impl JobScheduActor {
                                         break;
                                     }
                                     log::info!("train_job_dao::save success -> {:?}", job_data);
+                                    stats.train_jobs += 1;
                                 }
                             }
                         }
@@ -122,13 +273,31 @@ impl JobScheduActor {
             }
         }
     }
-    fn check_publish_job(&self) {
+    fn check_inventory(&self, group_id: Option<i32>, stats: &PassStats) {
+        let groups = match group_dao::list_all_auto_publish() {
+            Ok(groups) => groups.data,
+            Err(err) => {
+                log::warn!("group_dao::list_all_auto_publish err -> {:?}", err);
+                return;
+            }
+        };
+        for group in groups {
+            if group.auto_publish != 1 || group_id.is_some_and(|id| id != group.id) {
+                continue;
+            }
+            let skipped_slots = stats.starved_slots.get(&group.id).copied().unwrap_or(0);
+            if let Err(err) = material_inventory::check_alerts(&self.conn, &group, skipped_slots) {
+                log::warn!("group {} check_alerts err -> {:?}", group.id, err);
+            }
+        }
+    }
+    fn check_publish_job(&self, group_id: Option<i32>, stats: &mut PassStats) {
         //list all auto publish group
         let result = group_dao::list_all_auto_publish();
         if let Ok(data) = result {
             for group in data.data {
                 //check open auto publish
-                if group.auto_publish != 1 {
+                if group.auto_publish != 1 || group_id.is_some_and(|id| id != group.id) {
                     continue;
                 }
                 //get account in group
@@ -136,96 +305,113 @@ impl JobScheduActor {
                 if let Ok(account_data) = result {
                     //check has unfinish publish_job by account
                     for account in account_data.data {
-                        let start_times = group.publish_start_time.split(",");
-                        for start_time in start_times {
+                        let today = chrono::Local::now().naive_local().date();
+                        for start_time in upcoming_slots(&group.publish_start_time, today) {
                             let group_clone = group.clone();
-                            let today = chrono::Local::now()
-                                .naive_local()
-                                .format("%Y-%m-%d")
-                                .to_string();
-                            let start_time = format!("{} {}:00", today, start_time);
-                            //if start_time < now, continue
-                            if chrono::NaiveDateTime::parse_from_str(
-                                &start_time,
-                                "%Y-%m-%d %H:%M:%S",
-                            )
-                            .unwrap()
-                                < chrono::Local::now().naive_local()
-                            {
-                                continue;
-                            }
-                            let account = account.clone();
-                            let uesrname = account.username;
-                            let email = account.email;
-                            let id = account.id;
-                            if uesrname.is_none() {
-                                log::warn!("account.username is none");
-                                continue;
-                            }
-                            let username = uesrname.unwrap();
-                            if username.is_empty() {
-                                log::warn!("account.username is empty");
+                            if let Some(reason) = account_skip_reason(&account) {
+                                log::info!("account {} skipped: {}", account.id, reason);
+                                stats.skip(reason);
                                 continue;
                             }
-                            //check username is email
-                            if username.is_empty() || username == email {
-                                log::info!("username is empty or email,can't use it to publish");
+                            if let Some(reason) = slot_block_reason(&group, &start_time) {
+                                log::info!(
+                                    "group {} slot {} skipped: {}",
+                                    group.id,
+                                    start_time,
+                                    reason
+                                );
+                                stats.skip(&reason);
                                 continue;
                             }
-                            let result = publish_job_dao::count_job_by_account_today(
-                                id.clone(),
-                                start_time.clone(),
-                            );
+                            let id = account.id;
+                            let result =
+                                publish_job_dao::count_job_by_account_today(id, start_time.clone());
                             if let Ok(count) = result {
                                 let start_time = start_time.clone();
                                 if count == 0 {
-                                    let mut material: String = "".to_string();
-                                    if group_clone.publish_type == 1 {
-                                        let result = material_dao::count(Some(0), Some(group.id));
-                                        if let Ok(count) = result {
-                                            if count == 0 {
-                                                continue;
-                                            }
+                                    match slot_quota_reason(
+                                        &group_clone,
+                                        &account,
+                                        &start_time,
+                                        &[],
+                                    ) {
+                                        Ok(Some(reason)) => {
+                                            log::info!(
+                                                "account {} slot {} skipped: {}",
+                                                id,
+                                                start_time,
+                                                reason
+                                            );
+                                            stats.skip(reason);
+                                            continue;
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            log::warn!("slot_quota_reason err -> {:?}", e);
+                                            continue;
                                         }
+                                    }
+                                    let mut material = None;
+                                    if group_clone.publish_type == PublishType::VIDEO {
                                         //get material
-                                        let result = material_dao::get_and_use_one(
+                                        let result = material_selection::pick_and_use(
                                             &self.conn,
-                                            group_clone.id,
+                                            &group_clone,
+                                            id,
                                         );
-                                        //if err, break
-                                        if let Err(_) = result {
-                                            log::warn!("get_and_use_one err");
-                                            continue;
+                                        match result {
+                                            Ok(picked) => material = Some(picked),
+                                            Err(RunTimeError::NotFound) => {
+                                                stats.skip("no unused material");
+                                                *stats
+                                                    .starved_slots
+                                                    .entry(group_clone.id)
+                                                    .or_insert(0) += 1;
+                                                continue;
+                                            }
+                                            Err(e) => {
+                                                log::warn!("pick_and_use err -> {:?}", e);
+                                                stats.skip("no unused material");
+                                                continue;
+                                            }
                                         }
-                                        material = result.unwrap().name;
                                     }
 
                                     //create publish_job
                                     let group_clone = group.clone();
-                                    //random get a title
-                                    let title = group_clone.title.unwrap_or("".to_string());
-                                    let title_lines: Vec<&str> = title
-                                        .split("\n")
-                                        .filter(|line| !line.trim().is_empty())
-                                        .collect();
-
-                                    let random_line = title_lines.choose(&mut thread_rng());
-
-                                    let title = match random_line {
-                                        Some(line) => *line,
-                                        None => "",
-                                    };
+                                    let context = caption_template::publish_context(
+                                        &group_clone,
+                                        material.as_ref(),
+                                        account.username.as_deref().unwrap_or(""),
+                                        &start_time,
+                                    );
+                                    let title = caption_template::publish_caption(
+                                        &group_clone,
+                                        material.as_ref(),
+                                        &context,
+                                    );
                                     let job_data = PublishJobData {
                                         id: None,
-                                        material: Some(material),
-                                        account_id: Some(id.clone()),
-                                        title: Some(title.to_string()),
-                                        status: Some(0),
+                                        material: Some(
+                                            material
+                                                .map(|material| material.name)
+                                                .unwrap_or_default(),
+                                        ),
+                                        account_id: Some(id),
+                                        title: Some(title),
+                                        //jobs of groups under review wait for approval
+                                        status: Some(if group_clone.requires_approval == Some(1) {
+                                            5
+                                        } else {
+                                            0
+                                        }),
                                         start_time: Some(start_time),
                                         group_id: Some(group_clone.id),
                                         publish_type: group_clone.publish_type,
                                         product_link: group_clone.product_link,
                                         remark: None,
+                                        last_error: None,
+                                        error_class: None,
                                     };
                                     let job_data_clone = job_data.clone();
                                     let result = publish_job_dao::save(&self.conn, job_data_clone);
@@ -237,6 +423,7 @@ impl JobScheduActor {
                                             "publish_job_dao::save success -> {:?}",
                                             job_data
                                         );
+                                        stats.publish_jobs += 1;
                                     }
                                 }
                             }
@@ -247,3 +434,174 @@ impl JobScheduActor {
         }
     }
 }
+
+/// Earliest time a pass can have something new to do: the first slots of
+/// tomorrow, a failed job whose retry backoff ends or a paused group resuming.
+fn next_due_time(now: NaiveDateTime) -> Result<NaiveDateTime, RunTimeError> {
+    let mut due = (now.date() + chrono::Duration::days(1))
+        .and_hms_opt(0, 0, 5)
+        .unwrap();
+    let retry_times = [
+        publish_job_dao::earliest_retry_time()?,
+        train_job_dao::earliest_retry_time()?,
+    ];
+    for group in group_dao::list_all()?.data {
+        if group.paused == 1 {
+            if let Some(resume_time) = group.resume_time {
+                if let Ok(resume_time) =
+                    NaiveDateTime::parse_from_str(&resume_time, "%Y-%m-%d %H:%M:%S")
+                {
+                    if resume_time > now {
+                        due = due.min(resume_time);
+                    }
+                }
+            }
+        }
+    }
+    for retry_time in retry_times.into_iter().flatten() {
+        if let Ok(retry_time) = NaiveDateTime::parse_from_str(&retry_time, "%Y-%m-%d %H:%M:%S") {
+            due = due.min(retry_time);
+        }
+    }
+    Ok(due)
+}
+
+/// Reason an account can't be used by the scheduler, None when it can.
+pub fn account_skip_reason(account: &AccountDetails) -> Option<&'static str> {
+    match AccountStatus::parse(&account.status) {
+        Some(AccountStatus::Active) => {}
+        Some(AccountStatus::NeedsLogin) => return Some("account needs_login"),
+        Some(AccountStatus::Locked) => return Some("account locked"),
+        Some(AccountStatus::Disabled) => return Some("account disabled"),
+        None => return Some("account status unknown"),
+    }
+    let username = match &account.username {
+        Some(username) => username,
+        None => return Some("username is none"),
+    };
+    if username.is_empty() {
+        return Some("username is empty");
+    }
+    //check username is email
+    if *username == account.email {
+        return Some("username is email, can't use it to publish");
+    }
+    None
+}
+
+/// Reason the slot starting at `start_time` must not get a job because the
+/// group or the whole scheduler is paused at that time.
+pub fn slot_block_reason(group: &GroupDetails, start_time: &str) -> Option<String> {
+    let at = NaiveDateTime::parse_from_str(start_time, "%Y-%m-%d %H:%M:%S").ok()?;
+    automation_pause::group_block_reason(group, at)
+}
+
+/// Reason the quotas of `account` leave the slot at `start_time` empty, shared
+/// by the scheduler, the preview and the calendar. `planned` are the posts the
+/// caller projected for the account and did not save.
+pub fn slot_quota_reason(
+    group: &GroupDetails,
+    account: &AccountDetails,
+    start_time: &str,
+    planned: &[NaiveDateTime],
+) -> Result<Option<&'static str>, RunTimeError> {
+    publish_quota::violation(Some(group), account, start_time, planned)
+}
+
+/// Expands the comma separated `HH:MM` slots of a group on `day`,
+/// dropping the slots that are already in the past.
+pub fn upcoming_slots(slots: &str, day: NaiveDate) -> Vec<String> {
+    let now = chrono::Local::now().naive_local();
+    slots
+        .split(',')
+        .filter_map(|slot| {
+            let start_time = format!("{} {}:00", day.format("%Y-%m-%d"), slot.trim());
+            match NaiveDateTime::parse_from_str(&start_time, "%Y-%m-%d %H:%M:%S") {
+                //if start_time < now, continue
+                Ok(time) if time >= now => Some(start_time),
+                Ok(_) => None,
+                Err(_) => {
+                    log::warn!("invalid slot: {}", slot);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Runs the publish expansion of `check_publish_job` for the next `days` days
+/// without writing anything, materials are picked with the group strategy and
+/// not handed out twice within the preview. Random strategies show one
+/// possible outcome.
+pub fn preview_publish_schedule(
+    group: &GroupDetails,
+    days: i64,
+) -> Result<Vec<SchedulePreviewItem>, RunTimeError> {
+    let accounts = account_dao::list_account_by_group_id(group.id)?.data;
+    let mut taken = HashSet::new();
+    let mut last_collection = material_dao::last_collection(group.id)?;
+    //posts the preview projected per account, they count against the quotas
+    let mut planned: HashMap<i32, Vec<NaiveDateTime>> = HashMap::new();
+    let today = chrono::Local::now().naive_local().date();
+    let mut data = Vec::new();
+    for day in 0..days {
+        let day = today + chrono::Duration::days(day);
+        for account in &accounts {
+            for start_time in upcoming_slots(&group.publish_start_time, day) {
+                let mut item = SchedulePreviewItem {
+                    account_id: account.id,
+                    username: account.username.clone(),
+                    start_time: start_time.clone(),
+                    material: None,
+                    skip_reason: None,
+                };
+                if let Some(reason) = account_skip_reason(account) {
+                    item.skip_reason = Some(reason.to_string());
+                } else if let Some(reason) = slot_block_reason(group, &start_time) {
+                    item.skip_reason = Some(reason);
+                } else if day == today
+                    && publish_job_dao::count_job_by_account_today(account.id, start_time.clone())?
+                        > 0
+                {
+                    item.skip_reason = Some("job already exists".to_string());
+                } else if let Some(reason) = slot_quota_reason(
+                    group,
+                    account,
+                    &start_time,
+                    planned
+                        .get(&account.id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                )? {
+                    item.skip_reason = Some(reason.to_string());
+                } else if group.publish_type == PublishType::VIDEO {
+                    let candidates = material_dao::list_candidates(
+                        group.id,
+                        account.id,
+                        group.reuse_after_days.unwrap_or(0),
+                    )?
+                    .into_iter()
+                    .filter(|material| !taken.contains(&material.id))
+                    .collect();
+                    match material_selection::pick(group, candidates, last_collection.as_deref()) {
+                        Some(material) => {
+                            taken.insert(material.id);
+                            last_collection = material.collection;
+                            item.material = Some(material.name);
+                        }
+                        None => item.skip_reason = Some("no unused material".to_string()),
+                    }
+                }
+                if item.skip_reason.is_none() {
+                    if let Ok(time) =
+                        NaiveDateTime::parse_from_str(&start_time, "%Y-%m-%d %H:%M:%S")
+                    {
+                        planned.entry(account.id).or_default().push(time);
+                    }
+                }
+                data.push(item);
+            }
+        }
+    }
+    Ok(data)
+}
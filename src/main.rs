@@ -1,6 +1,8 @@
+use crate::blob_gc::BlobGcActor;
 use crate::ddl_actor::DdlActor;
 use crate::ddl_actor::DdlMessage;
 use crate::job_schedu::JobScheduActor;
+use crate::material_import::ImportActor;
 use crate::offline_checker::OfflineCheckerActor;
 use actix::Actor;
 use actix_cors::Cors;
@@ -17,15 +19,30 @@ use flexi_logger::{FileSpec, WriteMode};
 use std::io;
 use std::sync::Arc;
 use std::sync::Mutex;
+mod account_transfer;
+mod automation_pause;
+mod blob_gc;
+mod blob_store;
+mod caption_template;
 mod dao;
 mod database;
 mod ddl_actor;
+mod job_reaper;
 mod job_schedu;
+mod material_import;
+mod material_inventory;
+mod material_selection;
 mod models;
 mod offline_checker;
+mod publish_calendar;
+mod publish_quota;
 mod request_util;
+mod retry_policy;
 mod routes;
 mod runtime_err;
+mod storage;
+mod upload;
+mod video_meta;
 #[actix_web::main]
 async fn main() -> io::Result<()> {
     // initialize logger
@@ -49,11 +66,23 @@ async fn main() -> io::Result<()> {
     let conn = database::get_conn().expect("get sqlite connection error");
     let conn_mutex = Mutex::new(conn);
     let conn_data = web::Data::new(conn_mutex);
-    JobScheduActor {
+    let schedu_addr = JobScheduActor::new(conn_data.clone()).start();
+    let schedu_data = web::Data::new(schedu_addr.clone());
+    let _addr = OfflineCheckerActor {
         conn: conn_data.clone(),
+        schedu: schedu_addr.clone(),
     }
     .start();
-    let _addr = OfflineCheckerActor {}.start();
+    let _gc_addr = BlobGcActor {
+        conn: conn_data.clone(),
+    }
+    .start();
+    let import_addr = ImportActor {
+        conn: conn_data.clone(),
+        schedu: schedu_addr.clone(),
+    }
+    .start();
+    let import_data = web::Data::new(import_addr);
     let ddl_actor_addr = DdlActor {}.start();
     //创建一个消息通道
     let (tx, rx) = std::sync::mpsc::channel::<DdlMessage>();
@@ -75,13 +104,15 @@ async fn main() -> io::Result<()> {
             .wrap(cors)
             .app_data(conn_data.clone())
             .app_data(TempFileConfig::default().directory("./tmp"))
-            //默认限制50M上传,修改为10GB
+            //默认限制50M上传,修改为10GB; 文件落盘到./tmp, 内存中最多缓冲2MB
             .app_data(
                 MultipartFormConfig::default()
                     .total_limit(1024 * 1024 * 1024 * 10)
-                    .memory_limit(1024 * 1024 * 1024 * 10),
+                    .memory_limit(1024 * 1024 * 2),
             )
             .app_data(ddl_sender_data.clone())
+            .app_data(schedu_data.clone())
+            .app_data(import_data.clone())
             .service(routes::add_account_api)
             .service(routes::get_account_api)
             .service(routes::update_account_api)
@@ -91,10 +122,39 @@ async fn main() -> io::Result<()> {
             .service(routes::add_material_api)
             .service(routes::get_material_api)
             .service(routes::get_material_count_api)
+            .service(routes::get_material_duplicates_api)
+            .service(routes::get_material_collections_api)
+            .service(routes::update_material_meta_api)
+            .service(routes::get_storage_url_api)
+            .service(routes::get_publish_history_api)
+            .service(routes::get_account_publish_history_api)
+            .service(routes::import_account_api)
+            .service(routes::export_account_api)
+            .service(routes::report_account_status_api)
+            .service(routes::update_account_status_api)
+            .service(routes::get_account_status_history_api)
+            .service(routes::get_material_gc_api)
+            .service(routes::run_material_gc_api)
             .service(routes::update_material_api)
+            .service(routes::create_upload_api)
+            .service(routes::get_upload_api)
+            .service(routes::put_upload_chunk_api)
+            .service(routes::finalize_upload_api)
+            .service(routes::delete_upload_api)
+            .service(routes::add_import_api)
+            .service(routes::add_import_archive_api)
+            .service(routes::get_import_api)
+            .service(routes::get_import_by_id_api)
             .service(routes::delete_material_api)
             .service(routes::add_job_api)
             .service(routes::get_job_api)
+            .service(routes::complete_job_api)
+            .service(routes::approve_job_api)
+            .service(routes::reject_job_api)
+            .service(routes::approve_jobs_api)
+            .service(routes::reject_jobs_api)
+            .service(routes::get_pending_review_job_api)
+            .service(routes::get_published_video_api)
             .service(routes::update_job_api)
             .service(routes::delete_job_api)
             .service(routes::add_train_job_api)
@@ -112,6 +172,16 @@ async fn main() -> io::Result<()> {
             .service(routes::add_group_api)
             .service(routes::update_group_api)
             .service(routes::delete_group_api)
+            .service(routes::get_group_schedule_preview_api)
+            .service(routes::get_calendar_api)
+            .service(routes::update_group_pause_api)
+            .service(routes::caption_preview_api)
+            .service(routes::scheduler_run_now_api)
+            .service(routes::get_scheduler_run_api)
+            .service(routes::get_notification_api)
+            .service(routes::read_all_notification_api)
+            .service(routes::read_notification_api)
+            .service(routes::group_inventory_api)
             .service(routes::get_music_api)
             .service(routes::get_music_random_api)
             .service(routes::add_music_api)
@@ -140,6 +210,8 @@ async fn main() -> io::Result<()> {
             .service(routes::count_train_job_by_status_api)
             .service(routes::retry_all_train_job_api)
             .service(routes::retry_all_publish_job_api)
+            .service(routes::bulk_publish_job_api)
+            .service(routes::bulk_train_job_api)
             .service(routes::count_account_by_group_id_api)
             .service(routes::add_post_comment_api)
             .service(routes::add_post_comment_topic_api)
@@ -0,0 +1,408 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use actix::prelude::*;
+use actix_web::web;
+use rusqlite::Connection;
+
+use crate::{
+    blob_store,
+    dao::{group_dao, import_job_dao, material_dao},
+    job_schedu::{JobScheduActor, Wake},
+    models::{
+        DedupMode, ImportFileDetails, ImportJobDetails, MaterialData, MaterialMetaData,
+        MaterialUploadResult,
+    },
+    runtime_err::RunTimeError,
+    upload::{self, UploadLimits, UploadedFile},
+    video_meta,
+};
+
+/// Stores uploaded files as blobs and saves them as materials of `group_id`,
+/// shared by the multipart upload, the resumable upload and imports. A file
+/// that is not a video or breaks a group constraint fails the whole call.
+/// `labels` are set on every material created.
+pub fn register(
+    conn: &Mutex<Connection>,
+    files: Vec<UploadedFile>,
+    group_id: i32,
+    dedup: DedupMode,
+    labels: &MaterialMetaData,
+) -> Result<Vec<MaterialUploadResult>, RunTimeError> {
+    let group = match group_id {
+        0 => None,
+        _ => match group_dao::get_by_id(group_id) {
+            Ok(group) => Some(group),
+            Err(RunTimeError::NotFound) => None,
+            Err(e) => return Err(e),
+        },
+    };
+    //validate every file before storing any of them
+    let mut metas = Vec::new();
+    for f in &files {
+        let meta = video_meta::probe(f.path(), &f.file_name)?;
+        if let Some(group) = &group {
+            video_meta::check_group(&meta, group, &f.file_name)?;
+        }
+        metas.push(meta);
+    }
+    let mut materials: Vec<(UploadedFile, MaterialData)> = Vec::new();
+    for (f, meta) in files.into_iter().zip(metas) {
+        let path = blob_store::blob_path(&f.sha256, f.extension());
+        log::debug!("saving {} ({} bytes) to {path}", f.file_name, f.size);
+        let material = MaterialData {
+            id: None,
+            name: path,
+            md5: f.md5.clone(),
+            sha256: Some(f.sha256.clone()),
+            group_id,
+            file_size: Some(meta.file_size),
+            duration: Some(meta.duration),
+            width: Some(meta.width),
+            height: Some(meta.height),
+            codec: Some(meta.codec),
+            bitrate: Some(meta.bitrate),
+            tags: labels.tags.clone(),
+            collection: labels.collection.clone(),
+            caption: labels.caption.clone(),
+            hashtags: labels.hashtags.clone(),
+        };
+        materials.push((f, material));
+    }
+    material_dao::save(conn, materials, dedup)
+}
+
+/// Resolves the directory of a directory import. Only directories under
+/// `IMPORT_ROOT` can be imported, a relative `path` is taken from there.
+pub fn import_dir(path: &str) -> Result<PathBuf, RunTimeError> {
+    let root = std::env::var("IMPORT_ROOT").unwrap_or_default();
+    if root.trim().is_empty() {
+        return Err(RunTimeError::BadRequest(
+            "directory imports are disabled, IMPORT_ROOT is not set".to_string(),
+        ));
+    }
+    let root = std::fs::canonicalize(root.trim())
+        .map_err(|e| RunTimeError::new(&format!("invalid IMPORT_ROOT: {}", e)))?;
+    //symlinks and `..` are resolved before the prefix is checked
+    root.join(path)
+        .canonicalize()
+        .ok()
+        .filter(|dir| dir.starts_with(&root) && dir.is_dir())
+        .ok_or_else(|| {
+            RunTimeError::BadRequest(format!("{} is not a directory under the import root", path))
+        })
+}
+
+/// The import type of an uploaded archive and the suffix it is kept under.
+pub fn archive_type(file_name: &str) -> Option<(&'static str, &'static str)> {
+    let file_name = file_name.to_lowercase();
+    if file_name.ends_with(".zip") {
+        Some(("zip", ".zip"))
+    } else if file_name.ends_with(".tar") {
+        Some(("tar", ".tar"))
+    } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        Some(("tar", ".tar.gz"))
+    } else {
+        None
+    }
+}
+
+/// Runs import jobs one after another, jobs a restart interrupted are
+/// picked up again on start.
+pub struct ImportActor {
+    pub conn: web::Data<Mutex<Connection>>,
+    pub schedu: Addr<JobScheduActor>,
+}
+impl Actor for ImportActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        match import_job_dao::list_unfinished() {
+            Ok(ids) => {
+                for id in ids {
+                    ctx.notify(RunImport { id });
+                }
+            }
+            Err(e) => log::error!("list unfinished imports failed with error: {}", e),
+        }
+    }
+}
+
+pub struct RunImport {
+    pub id: i32,
+}
+impl Message for RunImport {
+    type Result = ();
+}
+impl Handler<RunImport> for ImportActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RunImport, ctx: &mut Context<Self>) -> Self::Result {
+        let conn = self.conn.clone();
+        let schedu = self.schedu.clone();
+        //`wait` holds back the next RunImport until this job is done
+        ctx.wait(
+            async move {
+                let group_ids = match web::block(move || run(&conn, msg.id)).await {
+                    Ok(group_ids) => group_ids,
+                    Err(e) => {
+                        log::error!("import {} failed with error: {}", msg.id, e);
+                        return;
+                    }
+                };
+                for group_id in group_ids {
+                    schedu.do_send(Wake {
+                        group_id: Some(group_id),
+                    });
+                }
+            }
+            .into_actor(self),
+        );
+    }
+}
+
+//runs a job to the end and records its outcome, returns the groups that got new materials
+fn run(conn: &Mutex<Connection>, id: i32) -> BTreeSet<i32> {
+    let mut group_ids = BTreeSet::new();
+    let job = match import_job_dao::get_by_id(id) {
+        Ok(job) => job,
+        Err(e) => {
+            log::error!("load import {} failed with error: {}", id, e);
+            return group_ids;
+        }
+    };
+    log::info!(
+        "import {} of {} {} started",
+        id,
+        job.source_type,
+        job.source
+    );
+    let result = import(conn, &job, &mut group_ids);
+    let error = result.as_ref().err().map(|e| e.to_string());
+    if let Err(e) = import_job_dao::finish(conn, id, error.as_deref()) {
+        log::error!("finish import {} failed with error: {}", id, e);
+    }
+    match error {
+        Some(error) => log::error!("import {} failed with error: {}", id, error),
+        None => log::info!("import {} finished", id),
+    }
+    if job.source_type != "directory" {
+        let _ = std::fs::remove_file(&job.source);
+    }
+    group_ids
+}
+
+fn import(
+    conn: &Mutex<Connection>,
+    job: &ImportJobDetails,
+    group_ids: &mut BTreeSet<i32>,
+) -> Result<(), RunTimeError> {
+    let dedup = DedupMode::parse(&job.dedup).unwrap_or(DedupMode::Skip);
+    let max_size = UploadLimits::from_env().max_file_size;
+    let groups: HashMap<String, i32> = group_dao::list_all()?
+        .data
+        .into_iter()
+        .map(|group| (group.name, group.id))
+        .collect();
+    let total = count_files(job)?;
+    import_job_dao::start(conn, job.id, total as i32)?;
+    visit_files(job, |path, reader| {
+        let mut report = ImportFileDetails {
+            path: path.to_string(),
+            group_id: job.group_id,
+            status: "failed".to_string(),
+            material_id: None,
+            message: None,
+        };
+        let result = reader.and_then(|reader| {
+            let group_id = target_group(job, path, &groups)?;
+            report.group_id = group_id;
+            let file_name = path.rsplit('/').next().unwrap_or(path).to_string();
+            let file = upload::receive_reader(file_name, reader, max_size)?;
+            register(
+                conn,
+                vec![file],
+                group_id,
+                dedup,
+                &MaterialMetaData::default(),
+            )
+        });
+        match result {
+            Ok(mut results) => {
+                if let Some(result) = results.pop() {
+                    report.material_id = result.id.or(result.duplicate_of);
+                    report.status = result.status;
+                }
+                if report.status == "created" {
+                    group_ids.insert(report.group_id);
+                }
+            }
+            //the file itself is at fault, report it and go on with the next one
+            Err(
+                e @ (RunTimeError::BadRequest(_)
+                | RunTimeError::PayloadTooLarge(_)
+                | RunTimeError::Conflict(_)
+                | RunTimeError::UnsupportedMediaType(_)
+                | RunTimeError::Unprocessable(_)),
+            ) => report.message = Some(e.to_string()),
+            Err(e) => return Err(e),
+        }
+        import_job_dao::add_file(conn, job.id, &report)
+    })
+}
+
+//files under a top-level folder go to the group of that name when subfolders are mapped
+fn target_group(
+    job: &ImportJobDetails,
+    path: &str,
+    groups: &HashMap<String, i32>,
+) -> Result<i32, RunTimeError> {
+    if job.map_subfolders == 0 {
+        return Ok(job.group_id);
+    }
+    match path.split_once('/') {
+        Some((folder, _)) => groups
+            .get(folder)
+            .copied()
+            .ok_or_else(|| RunTimeError::BadRequest(format!("no group named {}", folder))),
+        None => Ok(job.group_id),
+    }
+}
+
+//dot files and folders as well as the resource forks of macOS archives
+fn is_hidden(path: &str) -> bool {
+    path.split('/')
+        .any(|part| part.starts_with('.') || part == "__MACOSX")
+}
+
+//archive member names relative to the archive root
+fn entry_path(name: &str) -> String {
+    name.trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+fn walk_dir(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), RunTimeError> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if is_hidden(&relative) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk_dir(root, &path, files)?;
+        } else if file_type.is_file() {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+fn open_zip(path: &str) -> Result<zip::ZipArchive<BufReader<File>>, RunTimeError> {
+    zip::ZipArchive::new(BufReader::new(File::open(path)?))
+        .map_err(|e| RunTimeError::BadRequest(format!("invalid zip archive: {}", e)))
+}
+
+fn open_tar(path: &str) -> Result<tar::Archive<Box<dyn Read>>, RunTimeError> {
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn Read> = if path.ends_with(".gz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn tar_error(e: std::io::Error) -> RunTimeError {
+    RunTimeError::BadRequest(format!("invalid tar archive: {}", e))
+}
+
+fn count_files(job: &ImportJobDetails) -> Result<usize, RunTimeError> {
+    let mut total = 0;
+    match job.source_type.as_str() {
+        "directory" => {
+            let mut files = Vec::new();
+            walk_dir(Path::new(&job.source), Path::new(&job.source), &mut files)?;
+            total = files.len();
+        }
+        "zip" => {
+            let archive = open_zip(&job.source)?;
+            total = archive
+                .file_names()
+                .filter(|name| !name.ends_with('/') && !is_hidden(&entry_path(name)))
+                .count();
+        }
+        _ => {
+            let mut archive = open_tar(&job.source)?;
+            for entry in archive.entries().map_err(tar_error)? {
+                let entry = entry.map_err(tar_error)?;
+                let path = entry_path(&entry.path().map_err(tar_error)?.to_string_lossy());
+                if entry.header().entry_type().is_file() && !is_hidden(&path) {
+                    total += 1;
+                }
+            }
+        }
+    }
+    Ok(total)
+}
+
+//calls `visit` with the relative path and content of every file of the job source
+fn visit_files<F>(job: &ImportJobDetails, mut visit: F) -> Result<(), RunTimeError>
+where
+    F: FnMut(&str, Result<&mut dyn Read, RunTimeError>) -> Result<(), RunTimeError>,
+{
+    match job.source_type.as_str() {
+        "directory" => {
+            let root = PathBuf::from(&job.source);
+            let mut files = Vec::new();
+            walk_dir(&root, &root, &mut files)?;
+            for path in files {
+                match File::open(root.join(&path)) {
+                    Ok(mut file) => visit(&path, Ok(&mut file))?,
+                    //gone since the walk or unreadable, let the report say so
+                    Err(e) => visit(
+                        &path,
+                        Err(RunTimeError::BadRequest(format!("failed to open: {}", e))),
+                    )?,
+                }
+            }
+        }
+        "zip" => {
+            let mut archive = open_zip(&job.source)?;
+            for index in 0..archive.len() {
+                let mut entry = archive
+                    .by_index(index)
+                    .map_err(|e| RunTimeError::BadRequest(format!("invalid zip archive: {}", e)))?;
+                let path = entry_path(entry.name());
+                if entry.is_dir() || is_hidden(&path) {
+                    continue;
+                }
+                visit(&path, Ok(&mut entry))?;
+            }
+        }
+        _ => {
+            let mut archive = open_tar(&job.source)?;
+            for entry in archive.entries().map_err(tar_error)? {
+                let mut entry = entry.map_err(tar_error)?;
+                let path = entry_path(&entry.path().map_err(tar_error)?.to_string_lossy());
+                if !entry.header().entry_type().is_file() || is_hidden(&path) {
+                    continue;
+                }
+                visit(&path, Ok(&mut entry))?;
+            }
+        }
+    }
+    Ok(())
+}
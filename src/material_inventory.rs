@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use chrono::NaiveTime;
+use rusqlite::Connection;
+
+use crate::{
+    dao::{account_dao, material_dao, notification_dao},
+    job_schedu::account_skip_reason,
+    models::{GroupDetails, GroupInventory, PublishType},
+    runtime_err::RunTimeError,
+};
+
+/// Alert threshold of groups that do not set `inventory_alert_days`.
+pub const DEFAULT_ALERT_DAYS: i32 = 3;
+
+fn slots_per_day(publish_start_time: &str) -> i32 {
+    publish_start_time
+        .split(',')
+        .filter(|slot| NaiveTime::parse_from_str(slot.trim(), "%H:%M").is_ok())
+        .count() as i32
+}
+
+/// Unused materials of a group and how many days they last at the current
+/// schedule. Only auto publish groups that post materials consume them.
+pub fn group_inventory(group: &GroupDetails) -> Result<GroupInventory, RunTimeError> {
+    let unused_materials = material_dao::count(Some(0), Some(group.id))?;
+    let accounts = account_dao::list_account_by_group_id(group.id)?
+        .data
+        .iter()
+        .filter(|account| account_skip_reason(account).is_none())
+        .count() as i32;
+    let slots_per_day = slots_per_day(&group.publish_start_time);
+    let daily_demand = if group.auto_publish == 1 && group.publish_type == PublishType::VIDEO {
+        slots_per_day * accounts
+    } else {
+        0
+    };
+    let days_left = (daily_demand > 0)
+        .then(|| (unused_materials as f64 / daily_demand as f64 * 10.0).round() / 10.0);
+    let alert_days = group.inventory_alert_days.unwrap_or(DEFAULT_ALERT_DAYS);
+    //a group that reuses materials keeps posting when it runs out of new ones
+    let low = alert_days > 0
+        && group.reuse_after_days.unwrap_or(0) <= 0
+        && days_left.is_some_and(|days| days < alert_days as f64);
+    Ok(GroupInventory {
+        group_id: group.id,
+        group_name: group.name.clone(),
+        unused_materials,
+        accounts,
+        slots_per_day,
+        daily_demand,
+        days_left,
+        alert_days,
+        low,
+    })
+}
+
+/// Raises the notifications of a group after a scheduler pass, `skipped_slots`
+/// is the number of slots the pass left empty for lack of material.
+pub fn check_alerts(
+    conn: &Mutex<Connection>,
+    group: &GroupDetails,
+    skipped_slots: i32,
+) -> Result<(), RunTimeError> {
+    if skipped_slots > 0 {
+        let message = format!(
+            "group {} skipped {} publish slots, it has no unused material left",
+            group.name, skipped_slots
+        );
+        if notification_dao::raise(conn, "material_exhausted", Some(group.id), &message)? {
+            log::warn!("{}", message);
+        }
+        return Ok(());
+    }
+    let inventory = group_inventory(group)?;
+    if inventory.low {
+        let message = format!(
+            "group {} has {} unused materials left, about {} days at {} posts a day",
+            group.name,
+            inventory.unused_materials,
+            inventory.days_left.unwrap_or(0.0),
+            inventory.daily_demand
+        );
+        if notification_dao::raise(conn, "low_inventory", Some(group.id), &message)? {
+            log::warn!("{}", message);
+        }
+    }
+    Ok(())
+}
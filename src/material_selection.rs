@@ -0,0 +1,106 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use rand::{distributions::WeightedIndex, prelude::*};
+use rusqlite::Connection;
+
+use crate::{
+    dao::material_dao,
+    models::{GroupDetails, MaterialDetails, SelectionStrategy},
+    runtime_err::RunTimeError,
+};
+
+//"funny:3,cats:1" -> {funny: 3, cats: 1}, tags are compared case-insensitively
+fn parse_tag_weights(tag_weights: &str) -> HashMap<String, u32> {
+    tag_weights
+        .split(',')
+        .filter_map(|pair| {
+            let (tag, weight) = pair.split_once(':')?;
+            Some((tag.trim().to_lowercase(), weight.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+//the summed weight of the material tags, 1 when none of them is weighted
+fn material_weight(material: &MaterialDetails, weights: &HashMap<String, u32>) -> u32 {
+    let tags = material.tags.as_deref().unwrap_or("");
+    let mut weighted = false;
+    let mut total = 0;
+    for tag in tags.split(',').map(|tag| tag.trim().to_lowercase()) {
+        if let Some(weight) = weights.get(&tag) {
+            weighted = true;
+            total += weight;
+        }
+    }
+    if weighted {
+        total
+    } else {
+        1
+    }
+}
+
+/// Picks one of `candidates`, which are ordered oldest first, with the group
+/// strategy. `last_collection` is the collection the group handed out last.
+pub fn pick(
+    group: &GroupDetails,
+    mut candidates: Vec<MaterialDetails>,
+    last_collection: Option<&str>,
+) -> Option<MaterialDetails> {
+    if candidates.is_empty() {
+        return None;
+    }
+    let strategy = group
+        .selection_strategy
+        .as_deref()
+        .and_then(SelectionStrategy::parse)
+        .unwrap_or(SelectionStrategy::Fifo);
+    let index = match strategy {
+        SelectionStrategy::Fifo => 0,
+        SelectionStrategy::Newest => candidates.len() - 1,
+        SelectionStrategy::Random => thread_rng().gen_range(0..candidates.len()),
+        SelectionStrategy::TagWeighted => {
+            let weights = parse_tag_weights(group.tag_weights.as_deref().unwrap_or(""));
+            let weights = candidates
+                .iter()
+                .map(|material| material_weight(material, &weights));
+            //all weights zero, fall back to the oldest
+            WeightedIndex::new(weights)
+                .map(|index| index.sample(&mut thread_rng()))
+                .unwrap_or(0)
+        }
+        SelectionStrategy::RoundRobin => {
+            let collection =
+                |material: &MaterialDetails| material.collection.clone().unwrap_or_default();
+            let mut collections: Vec<String> = candidates.iter().map(collection).collect();
+            collections.sort();
+            collections.dedup();
+            //the first collection after the last one, wrapping around. Materials
+            //without a collection take their turn as the "" collection
+            let last = last_collection.unwrap_or_default();
+            let next = collections
+                .iter()
+                .find(|name| name.as_str() > last)
+                .unwrap_or(&collections[0]);
+            candidates
+                .iter()
+                .position(|material| &collection(material) == next)
+                .unwrap_or(0)
+        }
+    };
+    Some(candidates.swap_remove(index))
+}
+
+/// Picks the next material `account_id` publishes and records it in the
+/// publish history, `NotFound` when the group has nothing left for it.
+pub fn pick_and_use(
+    conn: &Mutex<Connection>,
+    group: &GroupDetails,
+    account_id: i32,
+) -> Result<MaterialDetails, RunTimeError> {
+    let candidates =
+        material_dao::list_candidates(group.id, account_id, group.reuse_after_days.unwrap_or(0))?;
+    let last_collection = material_dao::last_collection(group.id)?;
+    let material =
+        pick(group, candidates, last_collection.as_deref()).ok_or(RunTimeError::NotFound)?;
+    material_dao::use_for_account(conn, &material, account_id)?;
+    Ok(material)
+}
@@ -1,5 +1,6 @@
 use actix_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AccountData {
@@ -10,6 +11,10 @@ pub struct AccountData {
     pub device: Option<String>,
     pub username: Option<String>,
     pub group_id: Option<i32>,
+    //quotas, unset uses the group quota and 0 lifts it
+    pub max_posts_per_day: Option<i32>,
+    pub max_posts_per_week: Option<i32>,
+    pub min_post_spacing: Option<i32>, //minutes
 }
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AccountDetails {
@@ -20,16 +25,97 @@ pub struct AccountDetails {
     pub device: Option<String>,
     pub username: Option<String>,
     pub group_id: Option<i32>,
+    pub max_posts_per_day: Option<i32>,
+    pub max_posts_per_week: Option<i32>,
+    pub min_post_spacing: Option<i32>,
+    //active, needs_login, locked or disabled
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub status_time: Option<String>,
+}
+/// Whether the scheduler may use an account. Agents report the first three,
+/// only an operator disables an account or enables it again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccountStatus {
+    Active,
+    NeedsLogin,
+    Locked,
+    Disabled,
+}
+impl AccountStatus {
+    pub fn parse(status: &str) -> Option<AccountStatus> {
+        match status.trim() {
+            "active" => Some(AccountStatus::Active),
+            "needs_login" => Some(AccountStatus::NeedsLogin),
+            "locked" => Some(AccountStatus::Locked),
+            "disabled" => Some(AccountStatus::Disabled),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::NeedsLogin => "needs_login",
+            AccountStatus::Locked => "locked",
+            AccountStatus::Disabled => "disabled",
+        }
+    }
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountStatusData {
+    //agents may name the account by username instead of id
+    pub id: Option<i32>,
+    pub username: Option<String>,
+    pub status: String,
+    pub reason: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountStatusHistory {
+    pub id: i32,
+    pub account_id: i32,
+    pub status: String,
+    pub previous_status: String,
+    pub reason: Option<String>,
+    //agent or operator
+    pub source: String,
+    pub create_time: String,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AccountResponseData {
     pub data: Vec<AccountDetails>,
 }
-#[derive(Debug, MultipartForm)]
-pub struct MaterialFormData {
-    #[multipart(limit = "10240 MiB")]
-    pub files: Vec<TempFile>,
-    pub group_id: Option<Text<i32>>,
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountImportData {
+    //csv or json, json content is an array of objects
+    pub format: String,
+    pub content: String,
+    //account field to the column holding it, unmapped fields use their own name
+    #[serde(default)]
+    pub mapping: HashMap<String, String>,
+    pub group_id: Option<i32>, //rows without a group_id
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountImportRow {
+    pub row: usize,
+    pub email: String,
+    pub username: String,
+    pub fans: i32,
+    pub device: String,
+    pub group_id: i32,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountImportError {
+    pub row: usize,
+    pub message: String,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountImportResult {
+    pub dry_run: bool,
+    pub total: usize,
+    pub imported: usize,
+    pub accounts: Vec<AccountImportRow>,
+    pub errors: Vec<AccountImportError>,
 }
 #[derive(Debug, MultipartForm)]
 pub struct InstallFormData {
@@ -42,12 +128,40 @@ pub struct MaterialData {
     pub id: Option<i32>,
     pub name: String,
     pub md5: String,
+    pub sha256: Option<String>,
     pub group_id: i32,
+    pub file_size: Option<i64>,
+    pub duration: Option<f64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub codec: Option<String>,
+    pub bitrate: Option<i64>,
+    pub tags: Option<String>,
+    pub collection: Option<String>,
+    pub caption: Option<String>,
+    pub hashtags: Option<String>,
+}
+/// Labels set on upload or edited later, an empty string clears a field on edit.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MaterialMetaData {
+    pub tags: Option<String>, //comma separated
+    pub collection: Option<String>,
+    pub caption: Option<String>, //published instead of a line of the group title
+    pub hashtags: Option<String>, //comma separated, with or without #
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MaterialCollectionDetails {
+    pub group_id: i32,
+    pub collection: String,
+    pub count: i32,
+    pub unused: i32,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MaterialUesData {
     pub name: String,
     pub used: i32,
+    //files are shared between groups, picks the one to update
+    pub group_id: Option<i32>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MaterialDetails {
@@ -56,12 +170,289 @@ pub struct MaterialDetails {
     pub md5: String,
     pub used: i32,
     pub group_id: i32,
+    pub sha256: Option<String>,
+    //video metadata, NULL for materials uploaded before it was extracted
+    pub duration: Option<f64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub codec: Option<String>,
+    pub bitrate: Option<i64>,
+    pub file_size: Option<i64>,
+    //comma separated
+    pub tags: Option<String>,
+    pub collection: Option<String>,
+    pub caption: Option<String>,
+    //comma separated, without #
+    pub hashtags: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MaterialResponseData {
     pub data: Vec<MaterialDetails>,
 }
+/// How auto-publish picks the next material of a group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionStrategy {
+    //oldest first
+    Fifo,
+    Random,
+    Newest,
+    //random, weighted by the group tag_weights of the material tags
+    TagWeighted,
+    //collections take turns, oldest first within a collection
+    RoundRobin,
+}
+impl SelectionStrategy {
+    pub fn parse(strategy: &str) -> Option<SelectionStrategy> {
+        match strategy.trim() {
+            "" | "fifo" => Some(SelectionStrategy::Fifo),
+            "random" => Some(SelectionStrategy::Random),
+            "newest" => Some(SelectionStrategy::Newest),
+            "tag_weighted" => Some(SelectionStrategy::TagWeighted),
+            "round_robin" => Some(SelectionStrategy::RoundRobin),
+            _ => None,
+        }
+    }
+}
+/// What a group or publish job posts, stored as the `publish_type` code
+/// with the fields its payload is read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PublishType {
+    //a material video, groups leave the material to the scheduler
+    Video { material: Option<String> },
+    //a product showcase of an http(s) link
+    Product { product_link: String },
+}
+impl PublishType {
+    pub const VIDEO: i32 = 1;
+    pub const PRODUCT: i32 = 2;
+
+    /// Reads the payload of `publish_type`, the error says what is wrong.
+    pub fn from_parts(
+        publish_type: i32,
+        material: Option<&str>,
+        product_link: Option<&str>,
+    ) -> Result<PublishType, String> {
+        match publish_type {
+            PublishType::VIDEO => Ok(PublishType::Video {
+                material: material
+                    .map(str::trim)
+                    .filter(|material| !material.is_empty())
+                    .map(str::to_string),
+            }),
+            PublishType::PRODUCT => {
+                let product_link = product_link.map(str::trim).unwrap_or_default();
+                match reqwest::Url::parse(product_link) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+                        Ok(PublishType::Product {
+                            product_link: product_link.to_string(),
+                        })
+                    }
+                    _ => Err(format!(
+                        "product_link must be an http or https url, got {:?}",
+                        product_link
+                    )),
+                }
+            }
+            _ => Err(format!(
+                "unknown publish_type {}, must be {} (video) or {} (product)",
+                publish_type,
+                PublishType::VIDEO,
+                PublishType::PRODUCT
+            )),
+        }
+    }
+}
+/// What a bulk operation does to the publish or train jobs its filter matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobBulkAction {
+    //pending, failed and pending review jobs move to status 7
+    Cancel,
+    Delete,
+    //move the start time of jobs that have not run yet
+    Reschedule,
+    //move jobs that have not run yet to another account
+    Reassign,
+    //failed, dead letter and canceled jobs run again
+    Retry,
+}
+impl JobBulkAction {
+    pub fn parse(action: &str) -> Option<JobBulkAction> {
+        match action.trim() {
+            "cancel" => Some(JobBulkAction::Cancel),
+            "delete" => Some(JobBulkAction::Delete),
+            "reschedule" => Some(JobBulkAction::Reschedule),
+            "reassign" => Some(JobBulkAction::Reassign),
+            "retry" => Some(JobBulkAction::Retry),
+            _ => None,
+        }
+    }
+}
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct JobFilter {
+    pub group_id: Option<i32>,
+    pub account_id: Option<i32>,
+    //device serial of the account
+    pub device: Option<String>,
+    pub status: Option<Vec<i32>>,
+    //start_time range, YYYY-MM-DD or YYYY-MM-DD HH:MM:SS, both ends included
+    pub start_from: Option<String>,
+    pub start_to: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobBulkData {
+    //cancel, delete, reschedule, reassign or retry
+    pub action: String,
+    #[serde(default)]
+    pub filter: JobFilter,
+    //reschedule: minutes to shift the start time by, may be negative
+    pub offset_minutes: Option<i64>,
+    //reschedule: new start time, YYYY-MM-DD HH:MM:SS
+    pub start_time: Option<String>,
+    //reassign: the account the jobs move to
+    pub account_id: Option<i32>,
+    //count the jobs without changing them
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobBulkResult {
+    pub action: String,
+    pub dry_run: bool,
+    pub count: usize,
+    pub ids: Vec<i32>,
+    //jobs a reschedule or reassign left alone, their target slot is taken
+    pub skipped: Vec<i32>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublishHistoryDetails {
+    pub id: i32,
+    pub account_id: i32,
+    pub material_id: i32,
+    pub group_id: i32,
+    pub create_time: String,
+}
+/// What an upload does with a file whose hash already exists in the group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupMode {
+    //fail the whole request with 409
+    Reject,
+    //drop the file and keep going
+    Skip,
+    //drop the file and answer with the existing material
+    Link,
+}
+impl DedupMode {
+    pub fn parse(mode: &str) -> Option<DedupMode> {
+        match mode.trim() {
+            "reject" => Some(DedupMode::Reject),
+            "skip" => Some(DedupMode::Skip),
+            "link" => Some(DedupMode::Link),
+            _ => None,
+        }
+    }
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MaterialUploadResult {
+    pub file_name: String,
+    //created, skipped or linked
+    pub status: String,
+    pub id: Option<i32>,
+    pub name: Option<String>,
+    pub duplicate_of: Option<i32>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UploadSessionData {
+    pub file_name: String,
+    //total size in bytes
+    pub size: i64,
+    pub group_id: Option<i32>,
+    //reject, skip or link, see DedupMode
+    pub dedup: Option<String>,
+    //checked against the assembled file on finalize when given
+    pub sha256: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UploadSessionDetails {
+    pub id: String,
+    pub file_name: String,
+    pub size: i64,
+    //bytes stored so far, the offset of the next chunk
+    pub received: i64,
+    pub group_id: i32,
+    pub dedup: Option<String>,
+    pub sha256: Option<String>,
+    pub create_time: String,
+    pub update_time: String,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BlobDetails {
+    pub sha256: String,
+    pub path: String,
+    pub size: i64,
+    pub ref_count: i32,
+}
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BlobGcReport {
+    pub dry_run: bool,
+    //blobs whose stored ref_count did not match the material rows
+    pub recounted: usize,
+    pub removed_blobs: Vec<String>,
+    //files under upload/material referenced by neither a blob nor a material
+    pub orphaned_files: Vec<String>,
+    pub removed_orphans: usize,
+    //rows whose file is missing on disk
+    pub dangling_materials: Vec<i32>,
+    pub dangling_blobs: Vec<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MaterialDuplicateDetails {
+    pub group_id: i32,
+    pub md5: String,
+    pub materials: Vec<MaterialDetails>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportJobData {
+    //server-local directory, not used for uploaded archives
+    pub path: Option<String>,
+    pub group_id: Option<i32>,
+    //1 to import files under a top-level folder into the group of that name
+    pub map_subfolders: Option<i32>,
+    //reject, skip or link, see DedupMode, skip by default
+    pub dedup: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportJobDetails {
+    pub id: i32,
+    //directory, zip or tar
+    pub source_type: String,
+    pub source: String,
+    pub group_id: i32,
+    pub map_subfolders: i32,
+    pub dedup: String,
+    //0 pending, 1 running, 2 finished, 3 failed
+    pub status: i32,
+    pub total: i32,
+    pub processed: i32,
+    pub created: i32,
+    //skipped or linked duplicates
+    pub duplicates: i32,
+    pub failed: i32,
+    pub error: Option<String>,
+    pub create_time: String,
+    pub update_time: String,
+    //per file report, only filled for a single job
+    pub files: Vec<ImportFileDetails>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportFileDetails {
+    //relative to the import directory or archive root
+    pub path: String,
+    pub group_id: i32,
+    //created, skipped, linked or failed
+    pub status: String,
+    pub material_id: Option<i32>,
+    pub message: Option<String>,
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PublishJobData {
@@ -75,6 +466,8 @@ pub struct PublishJobData {
     pub publish_type: i32,
     pub product_link: Option<String>,
     pub remark: Option<String>,
+    pub last_error: Option<String>,
+    pub error_class: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PublishJobDetails {
@@ -91,12 +484,140 @@ pub struct PublishJobDetails {
     pub product_link: Option<String>,
     pub username: Option<String>,
     pub remark: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub error_class: Option<String>,
+    pub next_retry_time: Option<String>,
+    pub run_time: Option<String>,
+    pub material_url: Option<String>,
+    pub video_id: Option<String>,
+    pub video_url: Option<String>,
+    pub publish_time: Option<String>,
+    pub platform_message: Option<String>,
+    pub reviewer: Option<String>,
+    pub review_comment: Option<String>,
+    pub review_time: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublishJobReviewData {
+    //jobs of a bulk review, the single job endpoints take the id from the path
+    pub ids: Option<Vec<i32>>,
+    pub reviewer: String,
+    pub comment: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublishJobCompleteData {
+    pub video_id: Option<String>,
+    pub video_url: Option<String>,
+    //YYYY-MM-DD HH:MM:SS, defaults to now
+    pub publish_time: Option<String>,
+    //what the platform said after posting, e.g. under review
+    pub message: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublishedVideoDetails {
+    pub id: i32,
+    pub publish_job_id: i32,
+    pub account_id: i32,
+    pub username: Option<String>,
+    pub group_id: i32,
+    pub material: String,
+    pub material_id: Option<i32>,
+    pub video_id: Option<String>,
+    pub video_url: Option<String>,
+    pub publish_time: String,
+    pub create_time: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountPublishHistory {
+    pub account_id: i32,
+    pub username: Option<String>,
+    //quotas in effect for the account, 0 for no limit
+    pub max_posts_per_day: i32,
+    pub max_posts_per_week: i32,
+    pub min_post_spacing: i32,
+    pub posts_today: i32,
+    //the 7 days ending today
+    pub posts_this_week: i32,
+    pub jobs: Vec<PublishJobDetails>,
+}
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PublishJobResponseData {
     pub data: Vec<PublishJobDetails>,
 }
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchedulePreviewItem {
+    pub account_id: i32,
+    pub username: Option<String>,
+    pub start_time: String,
+    pub material: Option<String>,
+    pub skip_reason: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CalendarEntry {
+    //job, or projected for a slot the scheduler has not filled yet
+    pub kind: String,
+    pub job_id: Option<i32>,
+    pub group_id: i32,
+    pub account_id: i32,
+    pub username: Option<String>,
+    pub start_time: String,
+    pub status: Option<i32>,
+    pub material: Option<String>,
+    //why a projected slot will not get a job
+    pub skip_reason: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CalendarHour {
+    pub hour: u32,
+    pub entries: Vec<CalendarEntry>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CalendarAccount {
+    pub account_id: i32,
+    pub username: Option<String>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CalendarGroupDay {
+    pub group_id: i32,
+    pub group_name: String,
+    pub jobs: i32,
+    pub projected: i32,
+    //projected slots that will be skipped
+    pub blocked: i32,
+    //accounts with neither a job nor a projected post that day
+    pub idle_accounts: Vec<CalendarAccount>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CalendarDay {
+    //YYYY-MM-DD
+    pub date: String,
+    pub hours: Vec<CalendarHour>,
+    pub groups: Vec<CalendarGroupDay>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchedulerRunData {
+    pub trigger: String,
+    pub group_id: Option<i32>,
+    pub duration_ms: i64,
+    pub publish_job_count: i32,
+    pub train_job_count: i32,
+    pub retry_count: i32,
+    pub skips: BTreeMap<String, i32>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchedulerRunDetails {
+    pub id: i32,
+    pub trigger: String,
+    pub group_id: Option<i32>,
+    pub duration_ms: i64,
+    pub publish_job_count: i32,
+    pub train_job_count: i32,
+    pub retry_count: i32,
+    pub skips: BTreeMap<String, i32>,
+    pub create_time: String,
+}
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TrainJobData {
     pub id: Option<i32>,
@@ -109,6 +630,8 @@ pub struct TrainJobData {
     pub start_time: Option<String>,
     pub duration: Option<i32>,
     pub remark: Option<String>,
+    pub last_error: Option<String>,
+    pub error_class: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TrainJobDetails {
@@ -125,11 +648,23 @@ pub struct TrainJobDetails {
     pub username: Option<String>,
     pub duration: i32,
     pub remark: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub error_class: Option<String>,
+    pub next_retry_time: Option<String>,
+    pub run_time: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TrainJobResponseData {
     pub data: Vec<TrainJobDetails>,
 }
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StuckJobDetails {
+    pub id: i32,
+    pub device: Option<String>,
+    pub online: Option<i32>,
+    pub running_seconds: i64,
+}
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DeviceData {
     pub serial: String,
@@ -184,6 +719,25 @@ pub struct GroupData {
     pub like_probable: i32,
     pub collect_probable: i32,
     pub train_duration: i32,
+    pub max_attempts: Option<i32>,
+    pub retry_delay: Option<i32>,
+    pub retry_error_classes: Option<String>, //comma separated, empty means all errors are retryable
+    pub publish_timeout: Option<i32>,
+    pub paused: Option<i32>,
+    pub resume_time: Option<String>,
+    pub maintenance_windows: Option<String>, //comma separated HH:MM-HH:MM
+    pub blackout_dates: Option<String>,      //comma separated YYYY-MM-DD
+    pub min_duration: Option<f64>,           //seconds, 0 for no limit
+    pub max_duration: Option<f64>,           //seconds, 0 for no limit
+    pub aspect_ratio: Option<String>,        //comma separated W:H, e.g. 9:16
+    pub selection_strategy: Option<String>,  //fifo, random, newest, tag_weighted or round_robin
+    pub tag_weights: Option<String>,         //comma separated tag:weight, e.g. funny:3,cats:1
+    pub reuse_after_days: Option<i32>,       //0 never reuses a material
+    pub inventory_alert_days: Option<i32>,   //days of material left that notify, 0 never
+    pub requires_approval: Option<i32>,      //1 creates publish jobs pending review
+    pub max_posts_per_day: Option<i32>,      //per account, 0 for no limit
+    pub max_posts_per_week: Option<i32>,     //per account, 0 for no limit
+    pub min_post_spacing: Option<i32>,       //minutes between posts of an account
 }
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GroupDetails {
@@ -201,6 +755,73 @@ pub struct GroupDetails {
     pub like_probable: i32,
     pub collect_probable: i32,
     pub train_duration: i32,
+    pub max_attempts: i32,
+    pub retry_delay: i32,
+    pub retry_error_classes: Option<String>,
+    pub publish_timeout: i32,
+    pub paused: i32,
+    pub resume_time: Option<String>,
+    pub maintenance_windows: Option<String>,
+    pub blackout_dates: Option<String>,
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    pub aspect_ratio: Option<String>,
+    pub selection_strategy: Option<String>,
+    pub tag_weights: Option<String>,
+    pub reuse_after_days: Option<i32>,
+    pub inventory_alert_days: Option<i32>,
+    pub requires_approval: Option<i32>,
+    pub max_posts_per_day: Option<i32>,
+    pub max_posts_per_week: Option<i32>,
+    pub min_post_spacing: Option<i32>,
+    //filled in by the group list, not stored
+    pub inventory: Option<GroupInventory>,
+}
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GroupInventory {
+    pub group_id: i32,
+    pub group_name: String,
+    pub unused_materials: i32,
+    //accounts the scheduler publishes with
+    pub accounts: i32,
+    pub slots_per_day: i32,
+    //materials the group publishes per day
+    pub daily_demand: i32,
+    //none when the group does not consume materials
+    pub days_left: Option<f64>,
+    pub alert_days: i32,
+    pub low: bool,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NotificationDetails {
+    pub id: i32,
+    pub kind: String,
+    pub group_id: Option<i32>,
+    pub message: String,
+    pub read: i32,
+    pub create_time: String,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CaptionPreviewData {
+    //defaults to what the scheduler would use for the group and material
+    pub template: Option<String>,
+    pub group_id: i32,
+    pub material_id: Option<i32>,
+    pub username: Option<String>,
+    //number of renderings, random blocks differ between them
+    pub count: Option<i32>,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CaptionPreviewItem {
+    pub template: String,
+    pub caption: String,
+    pub length: usize,
+    pub limit: usize,
+}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GroupPauseData {
+    pub paused: i32,
+    pub resume_time: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GroupResponseData {
@@ -251,6 +872,7 @@ pub struct AvatarData {
 pub struct AvatarDetails {
     pub id: i32,
     pub name: String,
+    pub url: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AvatarResponseData {
@@ -1,15 +1,27 @@
-use crate::{dao::device_dao, models::ResponseData};
+use crate::{
+    dao::device_dao,
+    job_reaper,
+    job_schedu::{JobScheduActor, Reschedule},
+    models::ResponseData,
+};
 use actix::prelude::*;
-use std::time::Duration;
+use actix_web::web;
+use rusqlite::Connection;
+use std::{sync::Mutex, time::Duration};
 
 use super::request_util;
-pub struct OfflineCheckerActor {}
+pub struct OfflineCheckerActor {
+    pub conn: web::Data<Mutex<Connection>>,
+    pub schedu: Addr<JobScheduActor>,
+}
 impl Actor for OfflineCheckerActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        let conn = self.conn.clone();
+        let schedu = self.schedu.clone();
         actix_rt::spawn(async move {
-            check().await;
+            check(conn, schedu).await;
         });
         self.schedule_check(ctx);
     }
@@ -17,15 +29,17 @@ impl Actor for OfflineCheckerActor {
 
 impl OfflineCheckerActor {
     fn schedule_check(&self, ctx: &mut <Self as Actor>::Context) {
-        ctx.run_interval(Duration::from_secs(10), move |_actor, _ctxx| {
+        ctx.run_interval(Duration::from_secs(10), move |actor, _ctxx| {
+            let conn = actor.conn.clone();
+            let schedu = actor.schedu.clone();
             actix_rt::spawn(async move {
-                check().await;
+                check(conn, schedu).await;
             });
         });
     }
 }
 
-async fn check() {
+async fn check(conn: web::Data<Mutex<Connection>>, schedu: Addr<JobScheduActor>) {
     log::debug!("check offline devices");
     let online_devices = device_dao::list_online_device(None, None);
     if online_devices.is_err() {
@@ -57,4 +71,12 @@ async fn check() {
             );
         }
     }
+    //fail or re-queue the jobs left running on offline devices
+    let result = web::block(move || job_reaper::reap(&conn)).await;
+    match result {
+        //reaped jobs may be due for a retry before the next planned pass
+        Ok(count) if count > 0 => schedu.do_send(Reschedule),
+        Ok(_) => {}
+        Err(e) => log::error!("reap stuck jobs failed with error: {}", e),
+    }
 }
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+
+use crate::{
+    dao::{account_dao, group_dao, publish_job_dao},
+    job_schedu::{account_skip_reason, slot_block_reason, slot_quota_reason, upcoming_slots},
+    models::{
+        AccountDetails, CalendarAccount, CalendarDay, CalendarEntry, CalendarGroupDay,
+        CalendarHour, GroupDetails,
+    },
+    runtime_err::RunTimeError,
+};
+
+/// Longest range one calendar request covers, in days.
+pub const MAX_DAYS: i64 = 62;
+
+//per group and day: (jobs, projected, blocked, accounts with a post)
+type GroupDayCounts = (i32, i32, i32, HashSet<i32>);
+
+fn hour_of(start_time: &str) -> u32 {
+    NaiveDateTime::parse_from_str(start_time, "%Y-%m-%d %H:%M:%S")
+        .map(|time| time.hour())
+        .unwrap_or(0)
+}
+
+/// The publish jobs between `from` and `to`, both included, with the slots
+/// the scheduler is going to fill, bucketed by day and hour.
+pub fn build(
+    from: NaiveDate,
+    to: NaiveDate,
+    group_id: Option<i32>,
+) -> Result<Vec<CalendarDay>, RunTimeError> {
+    let groups: Vec<GroupDetails> = group_dao::list_all()?
+        .data
+        .into_iter()
+        .filter(|group| group_id.is_none_or(|id| id == group.id))
+        .collect();
+    let mut accounts: BTreeMap<i32, Vec<AccountDetails>> = BTreeMap::new();
+    for group in &groups {
+        accounts.insert(
+            group.id,
+            account_dao::list_account_by_group_id(group.id)?.data,
+        );
+    }
+    let mut entries: BTreeMap<String, Vec<CalendarEntry>> = BTreeMap::new();
+    let mut counts: BTreeMap<(String, i32), GroupDayCounts> = BTreeMap::new();
+    let (range_from, range_to) = (format!("{} 00:00:00", from), format!("{} 23:59:59", to));
+    let jobs = publish_job_dao::list_between(&range_from, &range_to, group_id)?;
+    //a moved job still covers the slot it was planned for
+    let mut scheduled: HashSet<(i32, String)> =
+        publish_job_dao::list_moved_slots_between(&range_from, &range_to, group_id)?
+            .into_iter()
+            .collect();
+    for job in jobs {
+        let date = job.start_time.get(..10).unwrap_or_default().to_string();
+        scheduled.insert((job.account_id, job.start_time.clone()));
+        let count = counts.entry((date.clone(), job.group_id)).or_default();
+        count.0 += 1;
+        count.3.insert(job.account_id);
+        entries.entry(date).or_default().push(CalendarEntry {
+            kind: "job".to_string(),
+            job_id: Some(job.id),
+            group_id: job.group_id,
+            account_id: job.account_id,
+            username: job.username,
+            start_time: job.start_time,
+            status: Some(job.status),
+            material: Some(job.material).filter(|material| !material.is_empty()),
+            skip_reason: None,
+        });
+    }
+    //slots check_publish_job has not turned into jobs yet, projected posts
+    //count against the quotas of the later ones
+    let mut planned: HashMap<i32, Vec<NaiveDateTime>> = HashMap::new();
+    for group in groups.iter().filter(|group| group.auto_publish == 1) {
+        let mut day = from;
+        while day <= to {
+            let date = day.format("%Y-%m-%d").to_string();
+            for account in &accounts[&group.id] {
+                for start_time in upcoming_slots(&group.publish_start_time, day) {
+                    if scheduled.contains(&(account.id, start_time.clone())) {
+                        continue;
+                    }
+                    let account_planned = planned.entry(account.id).or_default();
+                    let mut skip_reason = account_skip_reason(account)
+                        .map(str::to_string)
+                        .or_else(|| slot_block_reason(group, &start_time));
+                    if skip_reason.is_none() {
+                        skip_reason =
+                            slot_quota_reason(group, account, &start_time, account_planned)?
+                                .map(str::to_string);
+                    }
+                    if skip_reason.is_none() {
+                        if let Ok(time) =
+                            NaiveDateTime::parse_from_str(&start_time, "%Y-%m-%d %H:%M:%S")
+                        {
+                            account_planned.push(time);
+                        }
+                    }
+                    let count = counts.entry((date.clone(), group.id)).or_default();
+                    if skip_reason.is_some() {
+                        count.2 += 1;
+                    } else {
+                        count.1 += 1;
+                        count.3.insert(account.id);
+                    }
+                    entries
+                        .entry(date.clone())
+                        .or_default()
+                        .push(CalendarEntry {
+                            kind: "projected".to_string(),
+                            job_id: None,
+                            group_id: group.id,
+                            account_id: account.id,
+                            username: account.username.clone(),
+                            start_time,
+                            status: None,
+                            material: None,
+                            skip_reason,
+                        });
+                }
+            }
+            day += chrono::Duration::days(1);
+        }
+    }
+    let mut days = Vec::new();
+    let mut day = from;
+    while day <= to {
+        let date = day.format("%Y-%m-%d").to_string();
+        let mut hours: BTreeMap<u32, Vec<CalendarEntry>> = BTreeMap::new();
+        for entry in entries.remove(&date).unwrap_or_default() {
+            hours
+                .entry(hour_of(&entry.start_time))
+                .or_default()
+                .push(entry);
+        }
+        let mut group_days = Vec::new();
+        for group in &groups {
+            let (jobs, projected, blocked, posting) =
+                counts.remove(&(date.clone(), group.id)).unwrap_or_default();
+            let idle_accounts: Vec<CalendarAccount> = accounts[&group.id]
+                .iter()
+                .filter(|account| !posting.contains(&account.id))
+                .map(|account| CalendarAccount {
+                    account_id: account.id,
+                    username: account.username.clone(),
+                })
+                .collect();
+            if jobs == 0 && projected == 0 && blocked == 0 && idle_accounts.is_empty() {
+                continue;
+            }
+            group_days.push(CalendarGroupDay {
+                group_id: group.id,
+                group_name: group.name.clone(),
+                jobs,
+                projected,
+                blocked,
+                idle_accounts,
+            });
+        }
+        //jobs of groups that no longer exist
+        for ((_, group_id), (jobs, projected, blocked, _)) in counts
+            .iter()
+            .filter(|((count_date, _), _)| *count_date == date)
+        {
+            group_days.push(CalendarGroupDay {
+                group_id: *group_id,
+                group_name: String::new(),
+                jobs: *jobs,
+                projected: *projected,
+                blocked: *blocked,
+                idle_accounts: Vec::new(),
+            });
+        }
+        days.push(CalendarDay {
+            date,
+            hours: hours
+                .into_iter()
+                .map(|(hour, mut entries)| {
+                    entries.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+                    CalendarHour { hour, entries }
+                })
+                .collect(),
+            groups: group_days,
+        });
+        day += chrono::Duration::days(1);
+    }
+    Ok(days)
+}
@@ -0,0 +1,87 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    dao::publish_job_dao,
+    models::{AccountDetails, GroupDetails},
+    runtime_err::RunTimeError,
+};
+
+/// Posting limits of an account, 0 for no limit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Quota {
+    pub max_posts_per_day: i32,
+    pub max_posts_per_week: i32,
+    //minutes
+    pub min_post_spacing: i32,
+}
+
+/// The account quotas, falling back to the quotas of its group.
+pub fn quota_for(group: Option<&GroupDetails>, account: &AccountDetails) -> Quota {
+    let limit = |account_value: Option<i32>, group_value: Option<i32>| {
+        account_value.or(group_value).unwrap_or(0).max(0)
+    };
+    Quota {
+        max_posts_per_day: limit(
+            account.max_posts_per_day,
+            group.and_then(|group| group.max_posts_per_day),
+        ),
+        max_posts_per_week: limit(
+            account.max_posts_per_week,
+            group.and_then(|group| group.max_posts_per_week),
+        ),
+        min_post_spacing: limit(
+            account.min_post_spacing,
+            group.and_then(|group| group.min_post_spacing),
+        ),
+    }
+}
+
+fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Reason a new post of `account` at `start_time` would break its quotas.
+/// `planned` are the posts a preview projected for the account without
+/// saving them, they count like jobs. The time is only parsed when a quota
+/// applies.
+pub fn violation(
+    group: Option<&GroupDetails>,
+    account: &AccountDetails,
+    start_time: &str,
+    planned: &[NaiveDateTime],
+) -> Result<Option<&'static str>, RunTimeError> {
+    let quota = quota_for(group, account);
+    if quota.max_posts_per_day == 0 && quota.max_posts_per_week == 0 && quota.min_post_spacing == 0
+    {
+        return Ok(None);
+    }
+    let at = NaiveDateTime::parse_from_str(start_time, "%Y-%m-%d %H:%M:%S").map_err(|_| {
+        RunTimeError::BadRequest("start_time must be YYYY-MM-DD HH:MM:SS".to_string())
+    })?;
+    let posts = |from: NaiveDateTime, to: NaiveDateTime| -> Result<i32, RunTimeError> {
+        let saved = publish_job_dao::count_posts(account.id, &format_time(from), &format_time(to))?;
+        let projected = planned
+            .iter()
+            .filter(|time| **time >= from && **time < to)
+            .count();
+        Ok(saved + projected as i32)
+    };
+    let day_start = at.date().and_hms_opt(0, 0, 0).unwrap_or(at);
+    let next_day = day_start + Duration::days(1);
+    if quota.max_posts_per_day > 0 && posts(day_start, next_day)? >= quota.max_posts_per_day {
+        return Ok(Some("daily post quota reached"));
+    }
+    //the 7 days ending on the day of the post
+    if quota.max_posts_per_week > 0
+        && posts(day_start - Duration::days(6), next_day)? >= quota.max_posts_per_week
+    {
+        return Ok(Some("weekly post quota reached"));
+    }
+    if quota.min_post_spacing > 0 {
+        let spacing = Duration::minutes(quota.min_post_spacing as i64);
+        if posts(at - spacing + Duration::seconds(1), at + spacing)? > 0 {
+            return Ok(Some("too close to another post"));
+        }
+    }
+    Ok(None)
+}
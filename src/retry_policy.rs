@@ -0,0 +1,67 @@
+use crate::models::GroupDetails;
+
+pub struct RetryPolicy {
+    pub max_attempts: i32,
+    pub retry_delay: i32,
+    pub retry_error_classes: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            retry_delay: 60,
+            retry_error_classes: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_group(group: &GroupDetails) -> RetryPolicy {
+        let retry_error_classes = group
+            .retry_error_classes
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(|class| class.trim().to_string())
+            .filter(|class| !class.is_empty())
+            .collect();
+        RetryPolicy {
+            max_attempts: group.max_attempts,
+            retry_delay: group.retry_delay,
+            retry_error_classes,
+        }
+    }
+    /// An empty class list means every error is retryable.
+    pub fn is_retryable(&self, error_class: Option<&str>) -> bool {
+        if self.retry_error_classes.is_empty() {
+            return true;
+        }
+        match error_class {
+            Some(error_class) => self.retry_error_classes.iter().any(|c| c == error_class),
+            None => false,
+        }
+    }
+    /// Delay before the next attempt, doubling after every failed attempt.
+    pub fn backoff_seconds(&self, attempts: i32) -> i64 {
+        let exponent = (attempts - 1).clamp(0, 16) as u32;
+        (self.retry_delay.max(0) as i64) * 2_i64.pow(exponent)
+    }
+    /// Returns the time the job may run again after its `attempts`-th failure,
+    /// or None when the job should be moved to the dead letter state (status 4).
+    pub fn next_retry_time(&self, attempts: i32, error_class: Option<&str>) -> Option<String> {
+        if attempts >= self.max_attempts || !self.is_retryable(error_class) {
+            return None;
+        }
+        let next = chrono::Local::now().naive_local()
+            + chrono::Duration::seconds(self.backoff_seconds(attempts));
+        Some(next.format("%Y-%m-%d %H:%M:%S").to_string())
+    }
+}
+
+pub fn policy_for_group(group_id: i32) -> RetryPolicy {
+    match crate::dao::group_dao::get_by_id(group_id) {
+        Ok(group) => RetryPolicy::from_group(&group),
+        Err(_) => RetryPolicy::default(),
+    }
+}
@@ -2,38 +2,52 @@ use crate::dao::comment_dao::{self, PostCommentData, PostCommentTopicData};
 use crate::dao::data_analytics_dao::DataAnalytics;
 use crate::dao::{
     account_dao, avatar_dao, data_analytics_dao, device_dao, dialog_watcher_dao, group_dao,
-    material_dao, music_dao, publish_job_dao, train_job_dao,
+    import_job_dao, job_bulk_dao, material_dao, music_dao, notification_dao, publish_history_dao,
+    publish_job_dao, published_video_dao, scheduler_run_dao, train_job_dao, upload_session_dao,
 };
 use crate::ddl_actor::DdlMessage;
+use crate::job_schedu::{self, JobScheduActor, Reschedule, RunNow, Wake};
+use crate::material_import::{self, ImportActor, RunImport};
 use crate::models::InstallFormData;
 use crate::models::{
-    AccountData, AvatarData, AvatarFormData, CommonResponse, DeviceData, DialogWatcherData,
-    GroupData, MaterialData, MaterialFormData, MaterialUesData, MusicData, PublishJobData,
-    ResponseData, TrainJobData,
+    AccountData, AccountImportData, AccountPublishHistory, AccountStatus, AccountStatusData,
+    AvatarData, AvatarFormData, CaptionPreviewData, CaptionPreviewItem, CommonResponse, DedupMode,
+    DeviceData, DialogWatcherData, GroupData, GroupPauseData, ImportJobData, JobBulkData,
+    MaterialMetaData, MaterialUesData, MaterialUploadResult, MusicData, PublishJobCompleteData,
+    PublishJobData, PublishJobReviewData, PublishType, ResponseData, SelectionStrategy,
+    TrainJobData, UploadSessionData,
 };
 use crate::request_util;
-use actix_multipart::form::MultipartForm;
+use crate::runtime_err::RunTimeError;
+use crate::upload::{self, SessionGuard, UploadLimits, UploadedFile};
+use crate::{
+    account_transfer, blob_gc, caption_template, material_inventory, publish_calendar,
+    publish_quota, storage,
+};
+use actix::Addr;
+use actix_multipart::{form::MultipartForm, Multipart};
 use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
 use local_ip_address::local_ip;
 use pickledb::{PickleDb, PickleDbDumpPolicy, SerializationMethod};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::io::Read;
-
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, fs::File};
 
 use uuid::Uuid;
 
 #[post("/api/account")]
 pub(crate) async fn add_account_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(account_data): web::Json<AccountData>,
 ) -> actix_web::Result<impl Responder> {
+    let group_id = account_data.group_id;
     web::block(move || account_dao::save(&conn, account_data)).await??;
+    schedu.do_send(Wake { group_id });
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
@@ -41,9 +55,11 @@ pub(crate) async fn add_account_api(
 #[put("/api/account")]
 pub(crate) async fn update_account_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(account_data): web::Json<AccountData>,
 ) -> actix_web::Result<impl Responder> {
     web::block(move || account_dao::update(&conn, account_data)).await??;
+    schedu.do_send(Wake { group_id: None });
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
@@ -93,6 +109,7 @@ pub(crate) async fn delete_account_api(
 #[get("/api/update_username")]
 pub(crate) async fn update_username_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Query(query): web::Query<HashMap<String, String>>,
 ) -> actix_web::Result<impl Responder> {
     let old_username = query
@@ -104,6 +121,7 @@ pub(crate) async fn update_username_api(
         .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing new_username query parameter"))?
         .clone();
     web::block(move || account_dao::update_username(&conn, &old_username, &new_username)).await??;
+    schedu.do_send(Wake { group_id: None });
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
@@ -111,6 +129,7 @@ pub(crate) async fn update_username_api(
 #[get("/api/update_username_device")]
 pub(crate) async fn update_username_device_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Query(query): web::Query<HashMap<String, String>>,
 ) -> actix_web::Result<impl Responder> {
     let username = query
@@ -122,6 +141,7 @@ pub(crate) async fn update_username_device_api(
         .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing device query parameter"))?
         .clone();
     web::block(move || account_dao::update_username_device(&conn, &username, &device)).await??;
+    schedu.do_send(Wake { group_id: None });
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
@@ -133,10 +153,16 @@ pub(crate) async fn install_api(
 ) -> actix_web::Result<impl Responder> {
     let serial = form.serial.as_ref().map(|s| s.0.clone());
     let file = form.file;
-    let file_name = file.file_name.unwrap();
-    let path = format!("upload/apk/{}", file_name);
-    file.file.persist(path).unwrap();
-    let url: String = format!("apk/{}", file_name);
+    let file_name = file
+        .file_name
+        .ok_or_else(|| RunTimeError::BadRequest("missing file name".to_string()))?;
+    let key = format!("apk/{}", file_name);
+    let url = web::block(move || -> Result<String, RunTimeError> {
+        let storage = storage::current();
+        storage.put_file(file.file.path(), &key)?;
+        storage.url(&key)
+    })
+    .await??;
     let devices = web::block(move || device_dao::list_online_device(serial, None)).await??;
     let task = devices.data.into_iter().map(|device| {
         let url = url.clone();
@@ -146,7 +172,7 @@ pub(crate) async fn install_api(
                 &format!(
                     "/api/device_install?serial={}&url={}",
                     device.serial.as_str(),
-                    url.as_str(),
+                    storage::uri_encode(&url, true),
                 ),
             )
             .await
@@ -167,44 +193,325 @@ pub(crate) async fn install_api(
 #[post("/api/material")]
 pub(crate) async fn add_material_api(
     conn: web::Data<Mutex<Connection>>,
-    MultipartForm(form): MultipartForm<MaterialFormData>,
-) -> actix_web::Result<impl Responder> {
-    let mut materials: Vec<MaterialData> = Vec::new();
-    let group_id = form.group_id.as_ref().map(|s| s.0.clone()).unwrap_or(0);
-    for f in form.files {
-        let file_name = f.file_name.unwrap();
-        let extension = Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("");
-        let name = format!("{}.{}", Uuid::new_v4(), extension);
-        let path = format!("upload/material/{}", name);
-        log::debug!("saving to {path}");
-        f.file.persist(path.clone()).unwrap();
-        let mut file = File::open(path).unwrap();
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).unwrap();
-        let md5 = format!("{:x}", md5::compute(&buffer));
-        materials.push(MaterialData {
-            id: None,
-            name: format!("material/{}", name),
-            md5,
-            group_id: group_id.clone(),
-        });
+    schedu: web::Data<Addr<JobScheduActor>>,
+    payload: Multipart,
+) -> actix_web::Result<impl Responder> {
+    let form = upload::receive(payload, &UploadLimits::from_env()).await?;
+    let group_id = match form.fields.get("group_id") {
+        Some(group_id) => group_id
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| RunTimeError::BadRequest("invalid group_id".to_string()))?,
+        None => 0,
+    };
+    let dedup = match form.fields.get("dedup") {
+        Some(mode) => DedupMode::parse(mode).ok_or_else(|| {
+            RunTimeError::BadRequest("dedup must be reject, skip or link".to_string())
+        })?,
+        None => DedupMode::Reject,
+    };
+    let labels = MaterialMetaData {
+        tags: form.fields.get("tags").cloned(),
+        collection: form.fields.get("collection").cloned(),
+        caption: form.fields.get("caption").cloned(),
+        hashtags: form.fields.get("hashtags").cloned(),
+    };
+    if let Some(caption) = &labels.caption {
+        caption_template::validate(caption)?;
     }
+    let results = register_materials(conn, form.files, group_id, dedup, labels).await?;
+    schedu.do_send(Wake {
+        group_id: Some(group_id),
+    });
+    Ok(web::Json(ResponseData { data: results }))
+}
 
-    web::block(move || material_dao::save(&conn, materials)).await??;
-    Ok(HttpResponse::Ok())
+//stores uploaded files as blobs and saves them as materials of `group_id`,
+//shared by the multipart and the resumable upload
+async fn register_materials(
+    conn: web::Data<Mutex<Connection>>,
+    files: Vec<UploadedFile>,
+    group_id: i32,
+    dedup: DedupMode,
+    labels: MaterialMetaData,
+) -> actix_web::Result<Vec<MaterialUploadResult>> {
+    let results =
+        web::block(move || material_import::register(&conn, files, group_id, dedup, &labels))
+            .await??;
+    Ok(results)
+}
+
+#[post("/api/upload")]
+pub(crate) async fn create_upload_api(
+    conn: web::Data<Mutex<Connection>>,
+    web::Json(session_data): web::Json<UploadSessionData>,
+) -> actix_web::Result<impl Responder> {
+    let limits = UploadLimits::from_env();
+    if session_data.size <= 0 {
+        return Err(RunTimeError::BadRequest("size must be positive".to_string()).into());
+    }
+    if session_data.size as u64 > limits.max_file_size {
+        return Err(RunTimeError::PayloadTooLarge(format!(
+            "{} exceeds the {} bytes file limit",
+            session_data.file_name, limits.max_file_size
+        ))
+        .into());
+    }
+    if let Some(dedup) = &session_data.dedup {
+        DedupMode::parse(dedup).ok_or_else(|| {
+            RunTimeError::BadRequest("dedup must be reject, skip or link".to_string())
+        })?;
+    }
+    let id = Uuid::new_v4().to_string();
+    let data = web::block(move || {
+        upload_session_dao::save(&conn, &id, &session_data)?;
+        upload_session_dao::get_by_id(&id)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/upload/{id}")]
+pub(crate) async fn get_upload_api(id: web::Path<String>) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    let data = web::block(move || upload_session_dao::get_by_id(&id)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+/// Appends a chunk at `offset`, which must equal the `received` count of the
+/// session. An optional `X-Chunk-Sha256` header is verified before the
+/// chunk is accepted.
+#[put("/api/upload/{id}")]
+pub(crate) async fn put_upload_chunk_api(
+    conn: web::Data<Mutex<Connection>>,
+    id: web::Path<String>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+    request: actix_web::HttpRequest,
+    payload: web::Payload,
+) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    let offset = query
+        .get("offset")
+        .and_then(|offset| offset.parse::<i64>().ok())
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing offset query parameter"))?;
+    let checksum = request
+        .headers()
+        .get("X-Chunk-Sha256")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    //read under the guard, so offset and received are not stale
+    let _guard = SessionGuard::acquire(&id)?;
+    let session_id = id.clone();
+    let session = web::block(move || upload_session_dao::get_by_id(&session_id)).await??;
+    if offset != session.received {
+        return Err(RunTimeError::Conflict(format!(
+            "offset {} does not match the {} bytes received",
+            offset, session.received
+        ))
+        .into());
+    }
+    let written = upload::append_chunk(
+        &upload::session_path(&id),
+        session.received as u64,
+        payload,
+        (session.size - session.received) as u64,
+        checksum.as_deref(),
+    )
+    .await?;
+    let received = session.received + written as i64;
+    let data = web::block(move || {
+        upload_session_dao::update_received(&conn, &id, received)?;
+        upload_session_dao::get_by_id(&id)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data }))
+}
+/// Registers a completely received upload as material of the session group,
+/// `dedup` may override the mode chosen when the session was created.
+#[post("/api/upload/{id}/finalize")]
+pub(crate) async fn finalize_upload_api(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    id: web::Path<String>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    let _guard = SessionGuard::acquire(&id)?;
+    let session_id = id.clone();
+    let session = web::block(move || upload_session_dao::get_by_id(&session_id)).await??;
+    if session.received != session.size {
+        return Err(RunTimeError::BadRequest(format!(
+            "upload incomplete, {} of {} bytes received",
+            session.received, session.size
+        ))
+        .into());
+    }
+    let dedup = match query.get("dedup").or(session.dedup.as_ref()) {
+        Some(mode) => DedupMode::parse(mode).ok_or_else(|| {
+            RunTimeError::BadRequest("dedup must be reject, skip or link".to_string())
+        })?,
+        None => DedupMode::Reject,
+    };
+    let session_id = id.clone();
+    let file_name = session.file_name.clone();
+    let file = web::block(move || upload::hash_file(file_name, upload::session_copy(&session_id)?))
+        .await??;
+    if let Some(sha256) = &session.sha256 {
+        if !sha256.eq_ignore_ascii_case(&file.sha256) {
+            return Err(RunTimeError::BadRequest(format!(
+                "file checksum mismatch, expected {} got {}",
+                sha256, file.sha256
+            ))
+            .into());
+        }
+    }
+    let results = register_materials(
+        conn.clone(),
+        vec![file],
+        session.group_id,
+        dedup,
+        MaterialMetaData::default(),
+    )
+    .await?;
+    //a failed finalize keeps the session, so it can be finalized again
+    web::block(move || {
+        let _ = std::fs::remove_file(upload::session_path(&id));
+        upload_session_dao::del(&conn, &id)
+    })
+    .await??;
+    schedu.do_send(Wake {
+        group_id: Some(session.group_id),
+    });
+    Ok(web::Json(ResponseData { data: results }))
+}
+#[delete("/api/upload/{id}")]
+pub(crate) async fn delete_upload_api(
+    conn: web::Data<Mutex<Connection>>,
+    id: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    let _guard = SessionGuard::acquire(&id)?;
+    web::block(move || {
+        upload_session_dao::get_by_id(&id)?;
+        let _ = std::fs::remove_file(upload::session_path(&id));
+        upload_session_dao::del(&conn, &id)
+    })
+    .await??;
+    Ok(HttpResponse::NoContent())
+}
+
+fn parse_dedup(dedup: Option<&String>) -> Result<String, RunTimeError> {
+    match dedup {
+        Some(mode) => DedupMode::parse(mode)
+            .map(|_| mode.trim().to_string())
+            .ok_or_else(|| {
+                RunTimeError::BadRequest("dedup must be reject, skip or link".to_string())
+            }),
+        //an import that is run again skips what it already imported
+        None => Ok("skip".to_string()),
+    }
+}
+
+#[post("/api/import")]
+pub(crate) async fn add_import_api(
+    conn: web::Data<Mutex<Connection>>,
+    importer: web::Data<Addr<ImportActor>>,
+    web::Json(import_data): web::Json<ImportJobData>,
+) -> actix_web::Result<impl Responder> {
+    let path = import_data
+        .path
+        .ok_or_else(|| RunTimeError::BadRequest("path is required".to_string()))?;
+    let dedup = parse_dedup(import_data.dedup.as_ref())?;
+    let group_id = import_data.group_id.unwrap_or(0);
+    let map_subfolders = import_data.map_subfolders.unwrap_or(0);
+    let job = web::block(move || {
+        let path = material_import::import_dir(&path)?;
+        let id = import_job_dao::save(
+            &conn,
+            "directory",
+            &path.to_string_lossy(),
+            group_id,
+            map_subfolders,
+            &dedup,
+        )?;
+        import_job_dao::get_by_id(id)
+    })
+    .await??;
+    importer.do_send(RunImport { id: job.id });
+    Ok(web::Json(ResponseData { data: job }))
+}
+#[post("/api/import/archive")]
+pub(crate) async fn add_import_archive_api(
+    conn: web::Data<Mutex<Connection>>,
+    importer: web::Data<Addr<ImportActor>>,
+    payload: Multipart,
+) -> actix_web::Result<impl Responder> {
+    let mut form = upload::receive(payload, &UploadLimits::from_env()).await?;
+    if form.files.len() != 1 {
+        return Err(RunTimeError::BadRequest("expected exactly one archive".to_string()).into());
+    }
+    let archive = form.files.remove(0);
+    let (source_type, suffix) = material_import::archive_type(&archive.file_name)
+        .ok_or_else(|| RunTimeError::BadRequest("archive must be a zip or tar file".to_string()))?;
+    let group_id = match form.fields.get("group_id") {
+        Some(group_id) => group_id
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| RunTimeError::BadRequest("invalid group_id".to_string()))?,
+        None => 0,
+    };
+    let map_subfolders = match form.fields.get("map_subfolders").map(|v| v.trim()) {
+        Some("1") | Some("true") => 1,
+        _ => 0,
+    };
+    let dedup = parse_dedup(form.fields.get("dedup"))?;
+    let job = web::block(move || {
+        //kept until the import is done, the upload itself is removed on drop
+        let source = format!("tmp/import-{}{}", uuid::Uuid::new_v4(), suffix);
+        std::fs::rename(archive.path(), &source).map_err(upload::storage_error)?;
+        let id = import_job_dao::save(
+            &conn,
+            source_type,
+            &source,
+            group_id,
+            map_subfolders,
+            &dedup,
+        )
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&source);
+        })?;
+        import_job_dao::get_by_id(id)
+    })
+    .await??;
+    importer.do_send(RunImport { id: job.id });
+    Ok(web::Json(ResponseData { data: job }))
+}
+#[get("/api/import")]
+pub(crate) async fn get_import_api() -> actix_web::Result<impl Responder> {
+    let data = web::block(import_job_dao::list).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/import/{id}")]
+pub(crate) async fn get_import_by_id_api(id: web::Path<i32>) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    let data = web::block(move || -> Result<_, RunTimeError> {
+        let mut job = import_job_dao::get_by_id(id)?;
+        job.files = import_job_dao::list_files(id)?;
+        Ok(job)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data }))
 }
 
 #[put("/api/material")]
 pub(crate) async fn update_material_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(material_data): web::Json<MaterialUesData>,
 ) -> actix_web::Result<impl Responder> {
     let name = material_data.name;
     let used: i32 = material_data.used;
-    web::block(move || material_dao::update(&conn, name, used)).await??;
+    let group_id = material_data.group_id;
+    let group_id = web::block(move || material_dao::update(&conn, name, group_id, used)).await??;
+    schedu.do_send(Wake { group_id });
     Ok(HttpResponse::NoContent())
 }
 #[get("/api/material")]
@@ -215,13 +522,253 @@ pub(crate) async fn get_material_api(
     let used = used.map(|s| s.parse::<i32>().unwrap_or(0));
     let group_id = query.get("group_id").cloned();
     let group_id = group_id.map(|s| s.parse::<i32>().unwrap_or(0));
-    let material_response_data = web::block(move || material_dao::list(used, group_id)).await??;
+    let tag = query.get("tag").cloned();
+    let collection = query.get("collection").cloned();
+    let material_response_data =
+        web::block(move || material_dao::list(used, group_id, tag, collection)).await??;
     Ok(web::Json(material_response_data))
 }
 #[derive(Serialize)]
 struct MaterialCountResponse {
     data: i32,
 }
+#[put("/api/material/{id}")]
+pub(crate) async fn update_material_meta_api(
+    conn: web::Data<Mutex<Connection>>,
+    id: web::Path<i32>,
+    web::Json(meta_data): web::Json<MaterialMetaData>,
+) -> actix_web::Result<impl Responder> {
+    let id = id.into_inner();
+    if let Some(caption) = &meta_data.caption {
+        caption_template::validate(caption)?;
+    }
+    let data = web::block(move || material_dao::update_meta(&conn, id, &meta_data)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/material/collections")]
+pub(crate) async fn get_material_collections_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let group_id = query.get("group_id").and_then(|s| s.parse::<i32>().ok());
+    let data = web::block(move || material_dao::list_collections(group_id)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/material/duplicates")]
+pub(crate) async fn get_material_duplicates_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let group_id = query.get("group_id").and_then(|s| s.parse::<i32>().ok());
+    let data = web::block(move || material_dao::list_duplicates(group_id)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/account/{id}/publish_history")]
+pub(crate) async fn get_account_publish_history_api(
+    path: web::Path<i32>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let days = match query.get("days") {
+        Some(days) => days
+            .parse::<i64>()
+            .map_err(|_| actix_web::error::ErrorBadRequest("Invalid days query parameter"))?,
+        None => 30,
+    };
+    if !(1..=365).contains(&days) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "days query parameter must be between 1 and 365",
+        ));
+    }
+    let history = web::block(move || -> Result<_, RunTimeError> {
+        let account = account_dao::get_by_id(id)?;
+        let group = match account.group_id {
+            Some(group_id) => group_dao::get_by_id(group_id).ok(),
+            None => None,
+        };
+        let quota = publish_quota::quota_for(group.as_ref(), &account);
+        let today = chrono::Local::now().date_naive();
+        let day = |offset: i64| format!("{} 00:00:00", today + chrono::Duration::days(offset));
+        Ok(AccountPublishHistory {
+            account_id: account.id,
+            username: account.username,
+            max_posts_per_day: quota.max_posts_per_day,
+            max_posts_per_week: quota.max_posts_per_week,
+            min_post_spacing: quota.min_post_spacing,
+            posts_today: publish_job_dao::count_posts(id, &day(0), &day(1))?,
+            posts_this_week: publish_job_dao::count_posts(id, &day(-6), &day(1))?,
+            jobs: publish_job_dao::list_by_account(id, &day(1 - days))?,
+        })
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data: history }))
+}
+async fn change_account_status(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    id: Option<i32>,
+    data: AccountStatusData,
+    source: &'static str,
+) -> actix_web::Result<impl Responder> {
+    let status = AccountStatus::parse(&data.status)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid status"))?;
+    let (group_id, changed) = web::block(move || -> Result<_, RunTimeError> {
+        let account = match (id.or(data.id), data.username.as_deref()) {
+            (Some(id), _) => account_dao::get_by_id(id)?,
+            (None, Some(username)) => account_dao::get_by_username(username.trim())?,
+            (None, None) => {
+                return Err(RunTimeError::BadRequest(
+                    "id or username is required".to_string(),
+                ))
+            }
+        };
+        let reason = data
+            .reason
+            .as_deref()
+            .map(str::trim)
+            .filter(|reason| !reason.is_empty());
+        let changed = account_dao::update_status(&conn, account.id, status, reason, source)?;
+        Ok((account.group_id, changed))
+    })
+    .await??;
+    //slots of a reactivated account are planned again
+    if changed && status == AccountStatus::Active {
+        schedu.do_send(Wake { group_id });
+    }
+    Ok(web::Json(ResponseData {
+        data: "ok".to_string(),
+    }))
+}
+#[post("/api/account/import")]
+pub(crate) async fn import_account_api(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    web::Json(data): web::Json<AccountImportData>,
+) -> actix_web::Result<impl Responder> {
+    let group_id = data.group_id;
+    let result = web::block(move || account_transfer::import(&conn, &data)).await??;
+    if result.imported > 0 {
+        schedu.do_send(Wake { group_id });
+    }
+    Ok(web::Json(ResponseData { data: result }))
+}
+/// Passwords are exported only with include_credentials=1 and an
+/// X-Admin-Token header that matches the ADMIN_TOKEN environment variable.
+#[get("/api/account/export")]
+pub(crate) async fn export_account_api(
+    request: actix_web::HttpRequest,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<HttpResponse> {
+    let format = query.get("format").map(String::as_str).unwrap_or("csv");
+    if format != "csv" && format != "json" {
+        return Err(actix_web::error::ErrorBadRequest(
+            "format must be csv or json",
+        ));
+    }
+    let group_id = match query.get("group_id") {
+        Some(group_id) => Some(
+            group_id
+                .parse::<i32>()
+                .map_err(|_| actix_web::error::ErrorBadRequest("Invalid group_id"))?,
+        ),
+        None => None,
+    };
+    let credentials = query.get("include_credentials").map(String::as_str) == Some("1");
+    if credentials {
+        let admin_token = std::env::var("ADMIN_TOKEN").unwrap_or_default();
+        let token = request
+            .headers()
+            .get("X-Admin-Token")
+            .and_then(|token| token.to_str().ok())
+            .unwrap_or_default();
+        if admin_token.is_empty() || token != admin_token {
+            return Err(actix_web::error::ErrorForbidden(
+                "exporting credentials needs a valid X-Admin-Token",
+            ));
+        }
+        log::warn!("accounts exported with credentials");
+    }
+    let accounts: Vec<_> = web::block(account_dao::list_all)
+        .await??
+        .data
+        .into_iter()
+        .filter(|account| group_id.is_none_or(|id| account.group_id == Some(id)))
+        .collect();
+    if format == "json" {
+        return Ok(HttpResponse::Ok().json(ResponseData {
+            data: account_transfer::export_json(&accounts, credentials),
+        }));
+    }
+    let body = account_transfer::export_csv(&accounts, credentials)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"accounts.csv\"",
+        ))
+        .body(body))
+}
+#[post("/api/account/status")]
+pub(crate) async fn report_account_status_api(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    web::Json(data): web::Json<AccountStatusData>,
+) -> actix_web::Result<impl Responder> {
+    change_account_status(conn, schedu, None, data, "agent").await
+}
+#[put("/api/account/{id}/status")]
+pub(crate) async fn update_account_status_api(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    path: web::Path<i32>,
+    web::Json(data): web::Json<AccountStatusData>,
+) -> actix_web::Result<impl Responder> {
+    change_account_status(conn, schedu, Some(path.into_inner()), data, "operator").await
+}
+#[get("/api/account/{id}/status_history")]
+pub(crate) async fn get_account_status_history_api(
+    path: web::Path<i32>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let data = web::block(move || -> Result<_, RunTimeError> {
+        account_dao::get_by_id(id)?;
+        account_dao::list_status_history(id)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/publish_history")]
+pub(crate) async fn get_publish_history_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let account_id = query.get("account_id").and_then(|s| s.parse::<i32>().ok());
+    let material_id = query.get("material_id").and_then(|s| s.parse::<i32>().ok());
+    let data = web::block(move || publish_history_dao::list(account_id, material_id)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/storage/url")]
+pub(crate) async fn get_storage_url_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let key = query
+        .get("key")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing key query parameter"))?
+        .clone();
+    let data = web::block(move || storage::current().url(&key)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[get("/api/material/gc")]
+pub(crate) async fn get_material_gc_api(
+    conn: web::Data<Mutex<Connection>>,
+) -> actix_web::Result<impl Responder> {
+    let data = web::block(move || blob_gc::collect(&conn, true)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+#[post("/api/material/gc")]
+pub(crate) async fn run_material_gc_api(
+    conn: web::Data<Mutex<Connection>>,
+) -> actix_web::Result<impl Responder> {
+    let data = web::block(move || blob_gc::collect(&conn, false)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
 #[get("/api/material/count")]
 pub(crate) async fn get_material_count_api(
     web::Query(query): web::Query<HashMap<String, String>>,
@@ -235,6 +782,7 @@ pub(crate) async fn get_material_count_api(
 }
 #[delete("/api/material")]
 pub(crate) async fn delete_material_api(
+    conn: web::Data<Mutex<Connection>>,
     web::Query(query): web::Query<HashMap<String, String>>,
 ) -> actix_web::Result<impl Responder> {
     let id = query
@@ -242,33 +790,232 @@ pub(crate) async fn delete_material_api(
         .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing id query parameter"))?
         .clone();
     let id: i32 = id.parse::<i32>().unwrap_or(0);
-    web::block(move || material_dao::del(id)).await??;
+    web::block(move || material_dao::del(&conn, id)).await??;
     Ok(HttpResponse::NoContent())
 }
 #[post("/api/publish_job")]
 pub(crate) async fn add_job_api(
     conn: web::Data<Mutex<Connection>>,
-    web::Json(job_data): web::Json<PublishJobData>,
+    web::Json(mut job_data): web::Json<PublishJobData>,
 ) -> actix_web::Result<impl Responder> {
-    let material = job_data.material.clone();
+    let publish_type = PublishType::from_parts(
+        job_data.publish_type,
+        job_data.material.as_deref(),
+        job_data.product_link.as_deref(),
+    )
+    .map_err(RunTimeError::BadRequest)?;
+    let material = match publish_type {
+        PublishType::Video { material: None } => {
+            return Err(RunTimeError::BadRequest("material is required".to_string()).into())
+        }
+        PublishType::Video { material } => material,
+        PublishType::Product { product_link } => {
+            job_data.product_link = Some(product_link);
+            None
+        }
+    };
+    job_data.material = Some(material.clone().unwrap_or_default());
     let conn_clone = conn.clone();
-    web::block(move || publish_job_dao::save(&conn_clone, job_data)).await??;
+    let material_clone = material.clone();
+    let group_id = web::block(move || -> Result<_, RunTimeError> {
+        //material files are shared between groups, a job without a group
+        //uses the one of its account
+        let group_id = match (job_data.group_id, job_data.account_id) {
+            (Some(group_id), _) => Some(group_id),
+            (None, Some(account_id)) => match account_dao::get_by_id(account_id) {
+                Ok(account) => account.group_id.filter(|group_id| *group_id != 0),
+                Err(RunTimeError::NotFound) => None,
+                Err(e) => return Err(e),
+            },
+            (None, None) => None,
+        };
+        job_data.group_id = group_id;
+        if let Some(material) = &material_clone {
+            if !material_dao::exists_by_name(material, group_id)? {
+                return Err(RunTimeError::BadRequest(match group_id {
+                    Some(group_id) => {
+                        format!("material {} does not exist in group {}", material, group_id)
+                    }
+                    None => format!("material {} does not exist", material),
+                }));
+            }
+        }
+        let group = match group_id {
+            Some(group_id) => Some(group_dao::get_by_id(group_id)?),
+            None => None,
+        };
+        if let (Some(account_id), Some(start_time)) = (job_data.account_id, &job_data.start_time) {
+            let account = account_dao::get_by_id(account_id)?;
+            if let Some(reason) =
+                publish_quota::violation(group.as_ref(), &account, start_time, &[])?
+            {
+                return Err(RunTimeError::Conflict(format!(
+                    "account {} {}",
+                    account_id, reason
+                )));
+            }
+        }
+        //jobs of groups under review wait for approval, whatever status was posted
+        if group.is_some_and(|group| group.requires_approval == Some(1)) {
+            job_data.status = Some(5);
+        }
+        publish_job_dao::save(&conn_clone, job_data)?;
+        Ok(group_id)
+    })
+    .await??;
     //update material used
-    let used = 1;
-    let conn_clone = conn.clone();
-    web::block(move || material_dao::update(&conn_clone, material.unwrap(), used)).await??;
+    if let Some(material) = material {
+        let used = 1;
+        let conn_clone = conn.clone();
+        web::block(move || material_dao::update(&conn_clone, material, group_id, used)).await??;
+    }
     Ok(HttpResponse::NoContent())
 }
 #[put("/api/publish_job")]
 pub(crate) async fn update_job_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(job_data): web::Json<PublishJobData>,
 ) -> actix_web::Result<impl Responder> {
+    let failed = job_data.status == Some(3);
     web::block(move || publish_job_dao::update(&conn, job_data)).await??;
+    if failed {
+        schedu.do_send(Reschedule);
+    }
+    Ok(web::Json(ResponseData {
+        data: "ok".to_string(),
+    }))
+}
+#[post("/api/publish_job/{id}/complete")]
+pub(crate) async fn complete_job_api(
+    conn: web::Data<Mutex<Connection>>,
+    path: web::Path<i32>,
+    web::Json(complete_data): web::Json<PublishJobCompleteData>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    if complete_data.video_id.is_none() && complete_data.video_url.is_none() {
+        return Err(
+            RunTimeError::BadRequest("video_id or video_url is required".to_string()).into(),
+        );
+    }
+    let publish_time = match &complete_data.publish_time {
+        Some(publish_time) => {
+            chrono::NaiveDateTime::parse_from_str(publish_time, "%Y-%m-%d %H:%M:%S").map_err(
+                |_| {
+                    RunTimeError::BadRequest("publish_time must be YYYY-MM-DD HH:MM:SS".to_string())
+                },
+            )?;
+            publish_time.clone()
+        }
+        None => chrono::Local::now()
+            .naive_local()
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+    };
+    web::block(move || publish_job_dao::complete(&conn, id, &complete_data, &publish_time))
+        .await??;
+    Ok(web::Json(ResponseData {
+        data: "ok".to_string(),
+    }))
+}
+#[get("/api/published_video")]
+pub(crate) async fn get_published_video_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let account_id = query.get("account_id").and_then(|s| s.parse::<i32>().ok());
+    let group_id = query.get("group_id").and_then(|s| s.parse::<i32>().ok());
+    let material_id = query.get("material_id").and_then(|s| s.parse::<i32>().ok());
+    let data =
+        web::block(move || published_video_dao::list(account_id, group_id, material_id)).await??;
+    Ok(web::Json(ResponseData { data }))
+}
+fn review_jobs(
+    conn: web::Data<Mutex<Connection>>,
+    ids: Vec<i32>,
+    approve: bool,
+    review_data: PublishJobReviewData,
+) -> Result<usize, RunTimeError> {
+    if review_data.reviewer.trim().is_empty() {
+        return Err(RunTimeError::BadRequest("reviewer is required".to_string()));
+    }
+    publish_job_dao::review(
+        &conn,
+        &ids,
+        approve,
+        review_data.reviewer.trim(),
+        review_data.comment.as_deref(),
+    )
+}
+async fn review_job(
+    conn: web::Data<Mutex<Connection>>,
+    id: i32,
+    approve: bool,
+    review_data: PublishJobReviewData,
+) -> actix_web::Result<impl Responder> {
+    web::block(move || -> Result<_, RunTimeError> {
+        if review_jobs(conn, vec![id], approve, review_data)? == 0 {
+            //missing, or not pending review
+            let status = publish_job_dao::get_status(id)?;
+            return Err(RunTimeError::Conflict(format!(
+                "publish_job {} is not pending review, status {}",
+                id, status
+            )));
+        }
+        Ok(())
+    })
+    .await??;
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
 }
+async fn review_job_bulk(
+    conn: web::Data<Mutex<Connection>>,
+    approve: bool,
+    review_data: PublishJobReviewData,
+) -> actix_web::Result<impl Responder> {
+    let ids = review_data
+        .ids
+        .clone()
+        .filter(|ids| !ids.is_empty())
+        .ok_or_else(|| RunTimeError::BadRequest("ids is required".to_string()))?;
+    let reviewed = web::block(move || review_jobs(conn, ids, approve, review_data)).await??;
+    Ok(web::Json(ResponseData { data: reviewed }))
+}
+#[post("/api/publish_job/{id}/approve")]
+pub(crate) async fn approve_job_api(
+    conn: web::Data<Mutex<Connection>>,
+    path: web::Path<i32>,
+    web::Json(review_data): web::Json<PublishJobReviewData>,
+) -> actix_web::Result<impl Responder> {
+    review_job(conn, path.into_inner(), true, review_data).await
+}
+#[post("/api/publish_job/{id}/reject")]
+pub(crate) async fn reject_job_api(
+    conn: web::Data<Mutex<Connection>>,
+    path: web::Path<i32>,
+    web::Json(review_data): web::Json<PublishJobReviewData>,
+) -> actix_web::Result<impl Responder> {
+    review_job(conn, path.into_inner(), false, review_data).await
+}
+#[post("/api/publish_job/approve")]
+pub(crate) async fn approve_jobs_api(
+    conn: web::Data<Mutex<Connection>>,
+    web::Json(review_data): web::Json<PublishJobReviewData>,
+) -> actix_web::Result<impl Responder> {
+    review_job_bulk(conn, true, review_data).await
+}
+#[post("/api/publish_job/reject")]
+pub(crate) async fn reject_jobs_api(
+    conn: web::Data<Mutex<Connection>>,
+    web::Json(review_data): web::Json<PublishJobReviewData>,
+) -> actix_web::Result<impl Responder> {
+    review_job_bulk(conn, false, review_data).await
+}
+#[get("/api/publish_job/pending_review")]
+pub(crate) async fn get_pending_review_job_api() -> actix_web::Result<impl Responder> {
+    let job_response_data = web::block(publish_job_dao::list_pending_review).await??;
+    Ok(web::Json(job_response_data))
+}
 #[get("/api/publish_job")]
 pub(crate) async fn get_job_api() -> actix_web::Result<impl Responder> {
     let job_response_data = web::block(move || publish_job_dao::list_all()).await??;
@@ -283,7 +1030,29 @@ pub(crate) async fn runable_publish_job_api(
         .get("agent_ip")
         .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing agent_ip query parameter"))?
         .clone();
-    let job_response_data = web::block(move || publish_job_dao::list_runable(agent_ip)).await??;
+    let job_response_data = web::block(move || -> Result<_, RunTimeError> {
+        let mut job_response_data = publish_job_dao::list_runable(agent_ip)?;
+        //the agent downloads the material from here, a signed url for object storage.
+        //A job whose url fails is left for the next poll instead of failing them all
+        let storage = storage::current();
+        job_response_data.data.retain_mut(|job| {
+            if job.material.is_empty() {
+                return true;
+            }
+            match storage.url(&job.material) {
+                Ok(url) => {
+                    job.material_url = Some(url);
+                    true
+                }
+                Err(e) => {
+                    log::warn!("publish_job {} material url err -> {:?}", job.id, e);
+                    false
+                }
+            }
+        });
+        Ok(job_response_data)
+    })
+    .await??;
     Ok(web::Json(job_response_data))
 }
 
@@ -312,9 +1081,14 @@ pub(crate) async fn add_train_job_api(
 #[put("/api/train_job")]
 pub(crate) async fn update_train_job_api(
     conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(job_data): web::Json<TrainJobData>,
 ) -> actix_web::Result<impl Responder> {
+    let failed = job_data.status == Some(3);
     web::block(move || train_job_dao::update(&conn, job_data)).await??;
+    if failed {
+        schedu.do_send(Reschedule);
+    }
     Ok(web::Json(ResponseData {
         data: "ok".to_string(),
     }))
@@ -396,23 +1170,101 @@ pub(crate) async fn get_device_init_api(
 
 #[get("/api/group")]
 pub(crate) async fn get_group_api() -> actix_web::Result<impl Responder> {
-    let group_response_data = web::block(move || group_dao::list_all()).await??;
+    let group_response_data = web::block(move || -> Result<_, RunTimeError> {
+        let mut group_response_data = group_dao::list_all()?;
+        for group in group_response_data.data.iter_mut() {
+            group.inventory = Some(material_inventory::group_inventory(group)?);
+        }
+        Ok(group_response_data)
+    })
+    .await??;
     Ok(web::Json(group_response_data))
 }
+fn check_group_data(group_data: &mut GroupData) -> Result<(), RunTimeError> {
+    let publish_type = PublishType::from_parts(
+        group_data.publish_type,
+        None,
+        group_data.product_link.as_deref(),
+    )
+    .map_err(RunTimeError::BadRequest)?;
+    if let PublishType::Product { product_link } = publish_type {
+        group_data.product_link = Some(product_link);
+    }
+    if let Some(strategy) = &group_data.selection_strategy {
+        SelectionStrategy::parse(strategy).ok_or_else(|| {
+            RunTimeError::BadRequest(
+                "selection_strategy must be fifo, random, newest, tag_weighted or round_robin"
+                    .to_string(),
+            )
+        })?;
+    }
+    //every title line is a caption template
+    for line in group_data.title.as_deref().unwrap_or("").lines() {
+        caption_template::validate(line)?;
+    }
+    Ok(())
+}
+#[post("/api/caption/preview")]
+pub(crate) async fn caption_preview_api(
+    web::Json(preview_data): web::Json<CaptionPreviewData>,
+) -> actix_web::Result<impl Responder> {
+    let count = preview_data.count.unwrap_or(5).clamp(1, 50);
+    let data = web::block(move || -> Result<_, RunTimeError> {
+        let group = group_dao::get_by_id(preview_data.group_id)?;
+        let material = match preview_data.material_id {
+            Some(id) => Some(material_dao::get_by_id(id)?),
+            None => None,
+        };
+        let start_time = chrono::Local::now()
+            .naive_local()
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        let context = caption_template::publish_context(
+            &group,
+            material.as_ref(),
+            preview_data.username.as_deref().unwrap_or("username"),
+            &start_time,
+        );
+        let mut data = Vec::new();
+        for _ in 0..count {
+            let template = match &preview_data.template {
+                Some(template) => template.clone(),
+                None => caption_template::publish_template(&group, material.as_ref()),
+            };
+            let caption = caption_template::render(&template, &context)?;
+            data.push(CaptionPreviewItem {
+                length: caption_template::caption_length(&caption),
+                limit: caption_template::CAPTION_LIMIT,
+                template,
+                caption,
+            });
+        }
+        Ok(data)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data }))
+}
 #[post("/api/group")]
 pub(crate) async fn add_group_api(
     conn: web::Data<Mutex<Connection>>,
-    web::Json(group_data): web::Json<GroupData>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    web::Json(mut group_data): web::Json<GroupData>,
 ) -> actix_web::Result<impl Responder> {
+    check_group_data(&mut group_data)?;
     web::block(move || group_dao::save(&conn, group_data)).await??;
+    schedu.do_send(Wake { group_id: None });
     Ok(HttpResponse::NoContent())
 }
 #[put("/api/group")]
 pub(crate) async fn update_group_api(
     conn: web::Data<Mutex<Connection>>,
-    web::Json(group_data): web::Json<GroupData>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    web::Json(mut group_data): web::Json<GroupData>,
 ) -> actix_web::Result<impl Responder> {
+    check_group_data(&mut group_data)?;
+    let group_id = group_data.id;
     web::block(move || group_dao::update(&conn, group_data)).await??;
+    schedu.do_send(Wake { group_id });
     Ok(HttpResponse::NoContent())
 }
 #[delete("/api/group")]
@@ -430,6 +1282,126 @@ pub(crate) async fn delete_group_api(
     web::block(move || group_dao::del(id)).await??;
     Ok(HttpResponse::NoContent())
 }
+#[get("/api/group/{id}/schedule_preview")]
+pub(crate) async fn get_group_schedule_preview_api(
+    path: web::Path<i32>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let days = match query.get("days") {
+        Some(days) => days
+            .parse::<i64>()
+            .map_err(|_| actix_web::error::ErrorBadRequest("Invalid days query parameter"))?,
+        None => 7,
+    };
+    if !(1..=31).contains(&days) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "days query parameter must be between 1 and 31",
+        ));
+    }
+    let preview = web::block(move || {
+        let group = group_dao::get_by_id(id)?;
+        job_schedu::preview_publish_schedule(&group, days)
+    })
+    .await??;
+    Ok(web::Json(ResponseData { data: preview }))
+}
+#[get("/api/calendar")]
+pub(crate) async fn get_calendar_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let parse_date = |name: &str| -> actix_web::Result<Option<chrono::NaiveDate>> {
+        query
+            .get(name)
+            .map(|date| {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                    actix_web::error::ErrorBadRequest(format!(
+                        "{} query parameter must be YYYY-MM-DD",
+                        name
+                    ))
+                })
+            })
+            .transpose()
+    };
+    let from = parse_date("from")?.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let to = parse_date("to")?.unwrap_or(from + chrono::Duration::days(6));
+    if to < from || (to - from).num_days() >= publish_calendar::MAX_DAYS {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "to must be after from and cover at most {} days",
+            publish_calendar::MAX_DAYS
+        )));
+    }
+    let group_id =
+        match query.get("group_id") {
+            Some(group_id) => Some(group_id.parse::<i32>().map_err(|_| {
+                actix_web::error::ErrorBadRequest("Invalid group_id query parameter")
+            })?),
+            None => None,
+        };
+    let days = web::block(move || publish_calendar::build(from, to, group_id)).await??;
+    Ok(web::Json(ResponseData { data: days }))
+}
+#[put("/api/group/{id}/pause")]
+pub(crate) async fn update_group_pause_api(
+    conn: web::Data<Mutex<Connection>>,
+    schedu: web::Data<Addr<JobScheduActor>>,
+    path: web::Path<i32>,
+    web::Json(pause_data): web::Json<GroupPauseData>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    web::block(move || group_dao::update_pause(&conn, id, pause_data)).await??;
+    schedu.do_send(Wake { group_id: Some(id) });
+    Ok(HttpResponse::NoContent())
+}
+#[post("/api/scheduler/run_now")]
+pub(crate) async fn scheduler_run_now_api(
+    schedu: web::Data<Addr<JobScheduActor>>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let group_id =
+        match query.get("group_id") {
+            Some(group_id) => Some(group_id.parse::<i32>().map_err(|_| {
+                actix_web::error::ErrorBadRequest("Invalid group_id query parameter")
+            })?),
+            None => None,
+        };
+    let run = schedu
+        .send(RunNow { group_id })
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)??;
+    Ok(web::Json(ResponseData { data: run }))
+}
+#[get("/api/scheduler/run")]
+pub(crate) async fn get_scheduler_run_api() -> actix_web::Result<impl Responder> {
+    let runs = web::block(scheduler_run_dao::list_recent).await??;
+    Ok(web::Json(ResponseData { data: runs }))
+}
+#[get("/api/notification")]
+pub(crate) async fn get_notification_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let unread = query.get("unread").is_some_and(|unread| unread == "1");
+    let notifications = web::block(move || notification_dao::list(unread)).await??;
+    Ok(web::Json(ResponseData {
+        data: notifications,
+    }))
+}
+#[put("/api/notification/{id}/read")]
+pub(crate) async fn read_notification_api(
+    conn: web::Data<Mutex<Connection>>,
+    path: web::Path<i32>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    web::block(move || notification_dao::mark_read(&conn, Some(id))).await??;
+    Ok(HttpResponse::NoContent())
+}
+#[put("/api/notification/read")]
+pub(crate) async fn read_all_notification_api(
+    conn: web::Data<Mutex<Connection>>,
+) -> actix_web::Result<impl Responder> {
+    web::block(move || notification_dao::mark_read(&conn, None)).await??;
+    Ok(HttpResponse::NoContent())
+}
 #[get("/api/music")]
 pub(crate) async fn get_music_api() -> actix_web::Result<impl Responder> {
     let music_response_data = web::block(move || music_dao::list_all()).await??;
@@ -527,6 +1499,18 @@ struct Settings {
     openai_api_key: Option<String>,
     email_suffix: Option<String>,
     password: Option<String>,
+    scheduler_paused: Option<String>,
+    //upload size caps in MiB
+    max_upload_file_size: Option<String>,
+    max_upload_request_size: Option<String>,
+    //local or s3
+    storage_backend: Option<String>,
+    s3_endpoint: Option<String>,
+    s3_bucket: Option<String>,
+    s3_region: Option<String>,
+    s3_access_key: Option<String>,
+    s3_secret_key: Option<String>,
+    s3_url_expires: Option<String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 struct SettingsResponseData {
@@ -547,6 +1531,18 @@ pub(crate) async fn get_settings_api() -> actix_web::Result<impl Responder> {
     let openai_api_key = std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "".to_string());
     let email_suffix = std::env::var("EMAIL_SUFFIX").unwrap_or_else(|_| "".to_string());
     let password = std::env::var("PASSWORD").unwrap_or_else(|_| "".to_string());
+    let scheduler_paused = std::env::var("SCHEDULER_PAUSED").unwrap_or_else(|_| "0".to_string());
+    let max_upload_file_size =
+        std::env::var("MAX_UPLOAD_FILE_SIZE").unwrap_or_else(|_| "4096".to_string());
+    let max_upload_request_size =
+        std::env::var("MAX_UPLOAD_REQUEST_SIZE").unwrap_or_else(|_| "10240".to_string());
+    let storage_backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+    let s3_endpoint = std::env::var("S3_ENDPOINT").unwrap_or_default();
+    let s3_bucket = std::env::var("S3_BUCKET").unwrap_or_default();
+    let s3_region = std::env::var("S3_REGION").unwrap_or_default();
+    let s3_access_key = std::env::var("S3_ACCESS_KEY").unwrap_or_default();
+    let s3_secret_key = std::env::var("S3_SECRET_KEY").unwrap_or_default();
+    let s3_url_expires = std::env::var("S3_URL_EXPIRES").unwrap_or_else(|_| "3600".to_string());
     let settings = Settings {
         proxy_url: Some(proxy_url),
         server_url: Some(server_url),
@@ -559,6 +1555,16 @@ pub(crate) async fn get_settings_api() -> actix_web::Result<impl Responder> {
         openai_api_key: Some(openai_api_key),
         email_suffix: Some(email_suffix),
         password: Some(password),
+        scheduler_paused: Some(scheduler_paused),
+        max_upload_file_size: Some(max_upload_file_size),
+        max_upload_request_size: Some(max_upload_request_size),
+        storage_backend: Some(storage_backend),
+        s3_endpoint: Some(s3_endpoint),
+        s3_bucket: Some(s3_bucket),
+        s3_region: Some(s3_region),
+        s3_access_key: Some(s3_access_key),
+        s3_secret_key: Some(s3_secret_key),
+        s3_url_expires: Some(s3_url_expires),
     };
     Ok(web::Json(SettingsResponseData {
         code: 0,
@@ -567,10 +1573,12 @@ pub(crate) async fn get_settings_api() -> actix_web::Result<impl Responder> {
 }
 #[put("/api/settings")]
 pub(crate) async fn update_settings_api(
+    schedu: web::Data<Addr<JobScheduActor>>,
     web::Json(settings): web::Json<Settings>,
 ) -> actix_web::Result<impl Responder> {
     set_settings(&settings);
     setup_env();
+    schedu.do_send(Wake { group_id: None });
     Ok(HttpResponse::NoContent())
 }
 
@@ -604,6 +1612,31 @@ pub fn setup_env() {
     );
     std::env::set_var("EMAIL_SUFFIX", &settings.email_suffix.unwrap_or_default());
     std::env::set_var("PASSWORD", &settings.password.unwrap_or_default());
+    std::env::set_var(
+        "SCHEDULER_PAUSED",
+        settings.scheduler_paused.unwrap_or_default(),
+    );
+    std::env::set_var(
+        "MAX_UPLOAD_FILE_SIZE",
+        settings.max_upload_file_size.unwrap_or_default(),
+    );
+    std::env::set_var(
+        "MAX_UPLOAD_REQUEST_SIZE",
+        settings.max_upload_request_size.unwrap_or_default(),
+    );
+    std::env::set_var(
+        "STORAGE_BACKEND",
+        settings.storage_backend.unwrap_or_default(),
+    );
+    std::env::set_var("S3_ENDPOINT", settings.s3_endpoint.unwrap_or_default());
+    std::env::set_var("S3_BUCKET", settings.s3_bucket.unwrap_or_default());
+    std::env::set_var("S3_REGION", settings.s3_region.unwrap_or_default());
+    std::env::set_var("S3_ACCESS_KEY", settings.s3_access_key.unwrap_or_default());
+    std::env::set_var("S3_SECRET_KEY", settings.s3_secret_key.unwrap_or_default());
+    std::env::set_var(
+        "S3_URL_EXPIRES",
+        settings.s3_url_expires.unwrap_or_default(),
+    );
 
     // if cfg!(debug_assertions) {
     //     std::env::set_var("RUST_BACKTRACE", "1");
@@ -666,6 +1699,58 @@ fn set_settings(settings: &Settings) {
             db.set("password", password).unwrap();
         }
     }
+    if let Some(scheduler_paused) = &settings.scheduler_paused {
+        if !scheduler_paused.is_empty() {
+            db.set("scheduler_paused", scheduler_paused).unwrap();
+        }
+    }
+    if let Some(max_upload_file_size) = &settings.max_upload_file_size {
+        if !max_upload_file_size.is_empty() {
+            db.set("max_upload_file_size", max_upload_file_size)
+                .unwrap();
+        }
+    }
+    if let Some(max_upload_request_size) = &settings.max_upload_request_size {
+        if !max_upload_request_size.is_empty() {
+            db.set("max_upload_request_size", max_upload_request_size)
+                .unwrap();
+        }
+    }
+    if let Some(storage_backend) = &settings.storage_backend {
+        if !storage_backend.is_empty() {
+            db.set("storage_backend", storage_backend).unwrap();
+        }
+    }
+    if let Some(s3_endpoint) = &settings.s3_endpoint {
+        if !s3_endpoint.is_empty() {
+            db.set("s3_endpoint", s3_endpoint).unwrap();
+        }
+    }
+    if let Some(s3_bucket) = &settings.s3_bucket {
+        if !s3_bucket.is_empty() {
+            db.set("s3_bucket", s3_bucket).unwrap();
+        }
+    }
+    if let Some(s3_region) = &settings.s3_region {
+        if !s3_region.is_empty() {
+            db.set("s3_region", s3_region).unwrap();
+        }
+    }
+    if let Some(s3_access_key) = &settings.s3_access_key {
+        if !s3_access_key.is_empty() {
+            db.set("s3_access_key", s3_access_key).unwrap();
+        }
+    }
+    if let Some(s3_secret_key) = &settings.s3_secret_key {
+        if !s3_secret_key.is_empty() {
+            db.set("s3_secret_key", s3_secret_key).unwrap();
+        }
+    }
+    if let Some(s3_url_expires) = &settings.s3_url_expires {
+        if !s3_url_expires.is_empty() {
+            db.set("s3_url_expires", s3_url_expires).unwrap();
+        }
+    }
 }
 fn get_settings() -> Settings {
     let db = get_db();
@@ -702,6 +1787,26 @@ fn get_settings() -> Settings {
     let password = db
         .get::<String>("password")
         .unwrap_or_else(|| "123456".to_string());
+    let scheduler_paused = db
+        .get::<String>("scheduler_paused")
+        .unwrap_or_else(|| "0".to_string());
+    let max_upload_file_size = db
+        .get::<String>("max_upload_file_size")
+        .unwrap_or_else(|| "4096".to_string());
+    let max_upload_request_size = db
+        .get::<String>("max_upload_request_size")
+        .unwrap_or_else(|| "10240".to_string());
+    let storage_backend = db
+        .get::<String>("storage_backend")
+        .unwrap_or_else(|| "local".to_string());
+    let s3_endpoint = db.get::<String>("s3_endpoint").unwrap_or_default();
+    let s3_bucket = db.get::<String>("s3_bucket").unwrap_or_default();
+    let s3_region = db.get::<String>("s3_region").unwrap_or_default();
+    let s3_access_key = db.get::<String>("s3_access_key").unwrap_or_default();
+    let s3_secret_key = db.get::<String>("s3_secret_key").unwrap_or_default();
+    let s3_url_expires = db
+        .get::<String>("s3_url_expires")
+        .unwrap_or_else(|| "3600".to_string());
     Settings {
         proxy_url: Some(proxy_url),
         server_url: Some(server_url),
@@ -714,6 +1819,16 @@ fn get_settings() -> Settings {
         openai_api_key: Some(openai_api_key),
         email_suffix: Some(email_suffix),
         password: Some(password),
+        scheduler_paused: Some(scheduler_paused),
+        max_upload_file_size: Some(max_upload_file_size),
+        max_upload_request_size: Some(max_upload_request_size),
+        storage_backend: Some(storage_backend),
+        s3_endpoint: Some(s3_endpoint),
+        s3_bucket: Some(s3_bucket),
+        s3_region: Some(s3_region),
+        s3_access_key: Some(s3_access_key),
+        s3_secret_key: Some(s3_secret_key),
+        s3_url_expires: Some(s3_url_expires),
     }
 }
 
@@ -1003,32 +2118,36 @@ pub(crate) async fn add_avatar_api(
     conn: web::Data<Mutex<Connection>>,
     MultipartForm(form): MultipartForm<AvatarFormData>,
 ) -> actix_web::Result<impl Responder> {
-    let mut avatars: Vec<AvatarData> = Vec::new();
-    for f in form.files {
-        let file_name = f.file_name.unwrap();
-        let extension = Path::new(&file_name)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("");
-        let name = format!("{}.{}", Uuid::new_v4(), extension);
-        let path = format!("upload/avatar/{}", name);
-        log::debug!("saving to {path}");
-        f.file.persist(path.clone()).unwrap();
-        let mut file = File::open(path).unwrap();
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).unwrap();
-        avatars.push(AvatarData {
-            id: None,
-            name: format!("avatar/{}", name),
-        });
-    }
-
-    web::block(move || avatar_dao::save(&conn, avatars)).await??;
+    web::block(move || -> Result<(), RunTimeError> {
+        let storage = storage::current();
+        let mut avatars: Vec<AvatarData> = Vec::new();
+        for f in form.files {
+            let file_name = f.file_name.unwrap_or_default();
+            let extension = Path::new(&file_name)
+                .extension()
+                .and_then(std::ffi::OsStr::to_str)
+                .unwrap_or("");
+            let name = format!("avatar/{}.{}", Uuid::new_v4(), extension);
+            log::debug!("saving to {name}");
+            storage.put_file(f.file.path(), &name)?;
+            avatars.push(AvatarData { id: None, name });
+        }
+        avatar_dao::save(&conn, avatars)
+    })
+    .await??;
     Ok(HttpResponse::Ok())
 }
 #[get("/api/avatar")]
 pub(crate) async fn get_avatar_api() -> actix_web::Result<impl Responder> {
-    let avatar_response_data = web::block(move || avatar_dao::list_all()).await??;
+    let avatar_response_data = web::block(move || -> Result<_, RunTimeError> {
+        let mut avatar_response_data = avatar_dao::list_all()?;
+        let storage = storage::current();
+        for avatar in avatar_response_data.data.iter_mut() {
+            avatar.url = Some(storage.url(&avatar.name)?);
+        }
+        Ok(avatar_response_data)
+    })
+    .await??;
     Ok(web::Json(avatar_response_data))
 }
 #[delete("/api/avatar")]
@@ -1049,7 +2168,12 @@ pub(crate) async fn delete_avatar_api(
 }
 #[get("/api/avatar/random")]
 pub(crate) async fn get_avatar_random_api() -> actix_web::Result<impl Responder> {
-    let avatar_response_data = web::block(move || avatar_dao::random_one()).await??;
+    let avatar_response_data = web::block(move || -> Result<_, RunTimeError> {
+        let mut avatar = avatar_dao::random_one()?;
+        avatar.url = Some(storage::current().url(&avatar.name)?);
+        Ok(avatar)
+    })
+    .await??;
     Ok(web::Json(avatar_response_data))
 }
 #[get("/api/train_job/count_by_status")]
@@ -1076,6 +2200,21 @@ pub(crate) async fn count_online_device_api() -> actix_web::Result<impl Responde
         data: device_response_data,
     }))
 }
+#[get("/api/group/inventory")]
+pub(crate) async fn group_inventory_api() -> actix_web::Result<impl Responder> {
+    let inventory = web::block(move || -> Result<_, RunTimeError> {
+        group_dao::list_all()?
+            .data
+            .iter()
+            .map(material_inventory::group_inventory)
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await??;
+    Ok(web::Json(CommonResponse {
+        code: 0,
+        data: inventory,
+    }))
+}
 #[get("/api/account/count_all")]
 pub(crate) async fn count_all_account_api() -> actix_web::Result<impl Responder> {
     let account_response_data = web::block(move || account_dao::count_all()).await??;
@@ -1102,17 +2241,55 @@ pub(crate) async fn count_account_by_group_id_api(
         data: account_response_data,
     }))
 }
+//optional group_id and error_class filters of the bulk retry endpoints
+fn retry_filter(
+    query: &HashMap<String, String>,
+) -> actix_web::Result<(Option<i32>, Option<String>)> {
+    let group_id =
+        match query.get("group_id") {
+            Some(group_id) => Some(group_id.parse::<i32>().map_err(|_| {
+                actix_web::error::ErrorBadRequest("Invalid group_id query parameter")
+            })?),
+            None => None,
+        };
+    let error_class = query.get("error_class").cloned();
+    Ok((group_id, error_class))
+}
+#[post("/api/publish_job/bulk")]
+pub(crate) async fn bulk_publish_job_api(
+    conn: web::Data<Mutex<Connection>>,
+    web::Json(bulk_data): web::Json<JobBulkData>,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || job_bulk_dao::run(&conn, "publish_job", &bulk_data)).await??;
+    Ok(web::Json(ResponseData { data: result }))
+}
+#[post("/api/train_job/bulk")]
+pub(crate) async fn bulk_train_job_api(
+    conn: web::Data<Mutex<Connection>>,
+    web::Json(bulk_data): web::Json<JobBulkData>,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || job_bulk_dao::run(&conn, "train_job", &bulk_data)).await??;
+    Ok(web::Json(ResponseData { data: result }))
+}
 #[get("/api/train_job/retry_all")]
-pub(crate) async fn retry_all_train_job_api() -> actix_web::Result<impl Responder> {
-    let device_response_data = web::block(move || train_job_dao::retry_all_failed()).await??;
+pub(crate) async fn retry_all_train_job_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let (group_id, error_class) = retry_filter(&query)?;
+    let device_response_data =
+        web::block(move || train_job_dao::retry_all_failed(group_id, error_class)).await??;
     Ok(web::Json(CommonResponse {
         code: 0,
         data: device_response_data,
     }))
 }
 #[get("/api/publish_job/retry_all")]
-pub(crate) async fn retry_all_publish_job_api() -> actix_web::Result<impl Responder> {
-    let device_response_data = web::block(move || publish_job_dao::retry_all_failed()).await??;
+pub(crate) async fn retry_all_publish_job_api(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> actix_web::Result<impl Responder> {
+    let (group_id, error_class) = retry_filter(&query)?;
+    let device_response_data =
+        web::block(move || publish_job_dao::retry_all_failed(group_id, error_class)).await??;
     Ok(web::Json(CommonResponse {
         code: 0,
         data: device_response_data,
@@ -1175,12 +2352,11 @@ pub(crate) async fn count_comment_job_by_status_api() -> actix_web::Result<impl
     }))
 }
 #[delete("/api/material/delete_all")]
-pub(crate) async fn delete_all_material_api() -> actix_web::Result<impl Responder> {
-    let device_response_data = web::block(move || material_dao::delete_all()).await??;
-    Ok(web::Json(CommonResponse {
-        code: 0,
-        data: device_response_data,
-    }))
+pub(crate) async fn delete_all_material_api(
+    conn: web::Data<Mutex<Connection>>,
+) -> actix_web::Result<impl Responder> {
+    web::block(move || material_dao::delete_all(&conn)).await??;
+    Ok(web::Json(CommonResponse { code: 0, data: () }))
 }
 ///api/train_job/delete_all
 #[delete("/api/train_job/delete_all")]
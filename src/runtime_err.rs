@@ -5,7 +5,24 @@ pub enum RunTimeError {
     ReqwestError(reqwest::Error),
     DatabaseError(rusqlite::Error),
     SerdeError(serde_json::Error),
+    IoError(std::io::Error),
     CustomError(String),
+    #[from(ignore)]
+    BadRequest(String),
+    #[from(ignore)]
+    PayloadTooLarge(String),
+    #[from(ignore)]
+    InsufficientStorage(String),
+    #[from(ignore)]
+    Conflict(String),
+    #[from(ignore)]
+    UnsupportedMediaType(String),
+    #[from(ignore)]
+    Unprocessable(String),
+    //a conflict with an existing record, answered with the record
+    #[from(ignore)]
+    #[display(fmt = "{}", _0)]
+    Duplicate(String, serde_json::Value),
     NotFound,
 }
 impl RunTimeError {
@@ -17,10 +34,37 @@ impl actix_web::ResponseError for RunTimeError {
     fn status_code(&self) -> StatusCode {
         match self {
             RunTimeError::NotFound => StatusCode::NOT_FOUND,
+            RunTimeError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            RunTimeError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            RunTimeError::InsufficientStorage(_) => StatusCode::INSUFFICIENT_STORAGE,
+            RunTimeError::Conflict(_) => StatusCode::CONFLICT,
+            RunTimeError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RunTimeError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            RunTimeError::Duplicate(_, _) => StatusCode::CONFLICT,
+            //a write that raced past the checks and hit a unique index
+            RunTimeError::DatabaseError(rusqlite::Error::SqliteFailure(err, _))
+                if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE =>
+            {
+                StatusCode::CONFLICT
+            }
             RunTimeError::SerdeError(_)
             | RunTimeError::DatabaseError(_)
             | RunTimeError::ReqwestError(_)
+            | RunTimeError::IoError(_)
             | RunTimeError::CustomError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+    fn error_response(&self) -> actix_web::HttpResponse {
+        match self {
+            RunTimeError::Duplicate(message, record) => {
+                actix_web::HttpResponse::build(self.status_code()).json(serde_json::json!({
+                    "message": message,
+                    "data": record,
+                }))
+            }
+            _ => actix_web::HttpResponse::build(self.status_code())
+                .content_type("text/plain; charset=utf-8")
+                .body(self.to_string()),
+        }
+    }
 }
@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{ErrorKind, Read, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use actix_multipart::Multipart;
+use actix_web::web;
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::runtime_err::RunTimeError;
+
+//text fields of an upload form (group_id, ...) are tiny, anything bigger is rejected
+const TEXT_FIELD_LIMIT: usize = 64 * 1024;
+
+pub struct UploadLimits {
+    pub max_file_size: u64,
+    pub max_request_size: u64,
+}
+impl UploadLimits {
+    /// Limits in MiB from the `MAX_UPLOAD_FILE_SIZE` and `MAX_UPLOAD_REQUEST_SIZE` settings.
+    pub fn from_env() -> UploadLimits {
+        let mib = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+                * 1024
+                * 1024
+        };
+        UploadLimits {
+            max_file_size: mib("MAX_UPLOAD_FILE_SIZE", 4096),
+            max_request_size: mib("MAX_UPLOAD_REQUEST_SIZE", 10240),
+        }
+    }
+}
+
+/// A file streamed to `tmp/`, removed again on drop unless it was moved away.
+pub struct UploadedFile {
+    pub file_name: String,
+    pub size: u64,
+    pub md5: String,
+    pub sha256: String,
+    path: PathBuf,
+}
+impl UploadedFile {
+    pub fn extension(&self) -> &str {
+        std::path::Path::new(&self.file_name)
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or("")
+    }
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+impl Drop for UploadedFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Default)]
+pub struct UploadForm {
+    pub files: Vec<UploadedFile>,
+    pub fields: HashMap<String, String>,
+}
+
+pub fn storage_error(e: std::io::Error) -> RunTimeError {
+    if e.kind() == ErrorKind::StorageFull {
+        return RunTimeError::InsufficientStorage("no space left on device".to_string());
+    }
+    RunTimeError::IoError(e)
+}
+
+/// Streams every file of a multipart request to disk, hashing the bytes as
+/// they arrive so a file is never held in memory.
+pub async fn receive(
+    mut payload: Multipart,
+    limits: &UploadLimits,
+) -> Result<UploadForm, RunTimeError> {
+    let mut form = UploadForm::default();
+    let mut total: u64 = 0;
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| RunTimeError::BadRequest(e.to_string()))?;
+        let name = field.name().to_string();
+        let file_name = field
+            .content_disposition()
+            .get_filename()
+            .map(|file_name| file_name.to_string());
+        let file_name = match file_name {
+            Some(file_name) => file_name,
+            None => {
+                let mut text = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    let chunk = chunk.map_err(|e| RunTimeError::BadRequest(e.to_string()))?;
+                    if text.len() + chunk.len() > TEXT_FIELD_LIMIT {
+                        return Err(RunTimeError::PayloadTooLarge(format!(
+                            "field {} is too large",
+                            name
+                        )));
+                    }
+                    text.extend_from_slice(&chunk);
+                }
+                form.fields
+                    .insert(name, String::from_utf8_lossy(&text).to_string());
+                continue;
+            }
+        };
+        let mut uploaded = UploadedFile {
+            file_name,
+            size: 0,
+            md5: String::new(),
+            sha256: String::new(),
+            path: PathBuf::from(format!("tmp/{}.part", Uuid::new_v4())),
+        };
+        let mut file = tokio::fs::File::create(&uploaded.path)
+            .await
+            .map_err(storage_error)?;
+        let mut md5_context = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| RunTimeError::BadRequest(e.to_string()))?;
+            uploaded.size += chunk.len() as u64;
+            total += chunk.len() as u64;
+            if uploaded.size > limits.max_file_size {
+                return Err(RunTimeError::PayloadTooLarge(format!(
+                    "{} exceeds the {} bytes file limit",
+                    uploaded.file_name, limits.max_file_size
+                )));
+            }
+            if total > limits.max_request_size {
+                return Err(RunTimeError::PayloadTooLarge(format!(
+                    "request exceeds the {} bytes limit",
+                    limits.max_request_size
+                )));
+            }
+            md5_context.consume(&chunk);
+            sha256.update(&chunk);
+            file.write_all(&chunk).await.map_err(storage_error)?;
+        }
+        //surface delayed write errors such as a full disk before accepting the file
+        file.sync_all().await.map_err(storage_error)?;
+        uploaded.md5 = format!("{:x}", md5_context.compute());
+        uploaded.sha256 = format!("{:x}", sha256.finalize());
+        form.files.push(uploaded);
+    }
+    Ok(form)
+}
+
+//sessions a chunk is currently written to or that are being finalized
+static BUSY_SESSIONS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Marks a session busy until dropped, a second request for the same
+/// session gets a conflict instead of interleaving its bytes.
+pub struct SessionGuard(String);
+impl SessionGuard {
+    pub fn acquire(id: &str) -> Result<SessionGuard, RunTimeError> {
+        let mut busy = BUSY_SESSIONS.lock().unwrap();
+        if !busy.insert(id.to_string()) {
+            return Err(RunTimeError::Conflict(format!(
+                "upload {} is busy with another request",
+                id
+            )));
+        }
+        Ok(SessionGuard(id.to_string()))
+    }
+}
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        BUSY_SESSIONS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Where the bytes of a resumable upload session are collected.
+pub fn session_path(id: &str) -> PathBuf {
+    PathBuf::from(format!("tmp/{}.part", id))
+}
+
+/// A working copy of the file of a session for registration, hard linked when
+/// the filesystem allows it, so the session keeps its bytes if registering the
+/// copy fails.
+pub fn session_copy(id: &str) -> Result<PathBuf, RunTimeError> {
+    let path = PathBuf::from(format!("tmp/{}.part", Uuid::new_v4()));
+    if std::fs::hard_link(session_path(id), &path).is_err() {
+        std::fs::copy(session_path(id), &path).map_err(storage_error)?;
+    }
+    Ok(path)
+}
+
+/// Writes the request body to `path` starting at `offset`. The file is cut
+/// back to `offset` when the body is larger than `max_len`, fails to arrive
+/// or its SHA-256 does not match `checksum`, so a failed chunk is sent again
+/// in full, also the part that arrived before a client went away. Returns
+/// the number of bytes written.
+pub async fn append_chunk(
+    path: &Path,
+    offset: u64,
+    mut payload: web::Payload,
+    max_len: u64,
+    checksum: Option<&str>,
+) -> Result<u64, RunTimeError> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .await
+        .map_err(storage_error)?;
+    //drop whatever an interrupted chunk left behind
+    file.set_len(offset).await.map_err(storage_error)?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(storage_error)?;
+    let mut written: u64 = 0;
+    let mut sha256 = Sha256::new();
+    let result: Result<(), RunTimeError> = async {
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.map_err(|e| RunTimeError::BadRequest(e.to_string()))?;
+            written += chunk.len() as u64;
+            if written > max_len {
+                return Err(RunTimeError::PayloadTooLarge(format!(
+                    "chunk exceeds the {} bytes left in the upload",
+                    max_len
+                )));
+            }
+            sha256.update(&chunk);
+            file.write_all(&chunk).await.map_err(storage_error)?;
+        }
+        if let Some(checksum) = checksum {
+            let actual = format!("{:x}", sha256.finalize_reset());
+            if !actual.eq_ignore_ascii_case(checksum.trim()) {
+                return Err(RunTimeError::BadRequest(format!(
+                    "chunk checksum mismatch, expected {} got {}",
+                    checksum, actual
+                )));
+            }
+        }
+        file.sync_all().await.map_err(storage_error)?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = file.set_len(offset).await;
+        return Err(e);
+    }
+    Ok(written)
+}
+
+/// Hashes a completely received file, the counterpart of `receive` for
+/// files assembled from chunks.
+pub fn hash_file(file_name: String, path: PathBuf) -> Result<UploadedFile, RunTimeError> {
+    let mut file = std::fs::File::open(&path)?;
+    let mut md5_context = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let mut size: u64 = 0;
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let len = file.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        size += len as u64;
+        md5_context.consume(&buffer[..len]);
+        sha256.update(&buffer[..len]);
+    }
+    Ok(UploadedFile {
+        file_name,
+        size,
+        md5: format!("{:x}", md5_context.compute()),
+        sha256: format!("{:x}", sha256.finalize()),
+        path,
+    })
+}
+
+/// Copies `reader` to `tmp/` while hashing it, used for files that come from
+/// the server itself, e.g. an import directory or archive.
+pub fn receive_reader(
+    file_name: String,
+    reader: &mut dyn Read,
+    max_size: u64,
+) -> Result<UploadedFile, RunTimeError> {
+    let mut uploaded = UploadedFile {
+        file_name,
+        size: 0,
+        md5: String::new(),
+        sha256: String::new(),
+        path: PathBuf::from(format!("tmp/{}.part", Uuid::new_v4())),
+    };
+    let mut file = std::fs::File::create(&uploaded.path).map_err(storage_error)?;
+    let mut md5_context = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let len = reader.read(&mut buffer).map_err(|e| {
+            RunTimeError::BadRequest(format!("failed to read {}: {}", uploaded.file_name, e))
+        })?;
+        if len == 0 {
+            break;
+        }
+        uploaded.size += len as u64;
+        if uploaded.size > max_size {
+            return Err(RunTimeError::PayloadTooLarge(format!(
+                "{} exceeds the {} bytes file limit",
+                uploaded.file_name, max_size
+            )));
+        }
+        md5_context.consume(&buffer[..len]);
+        sha256.update(&buffer[..len]);
+        file.write_all(&buffer[..len]).map_err(storage_error)?;
+    }
+    file.sync_all().map_err(storage_error)?;
+    uploaded.md5 = format!("{:x}", md5_context.compute());
+    uploaded.sha256 = format!("{:x}", sha256.finalize());
+    Ok(uploaded)
+}
@@ -0,0 +1,119 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use mp4::TrackType;
+
+use crate::{models::GroupDetails, runtime_err::RunTimeError};
+
+//relative difference tolerated between a video and a required aspect ratio
+const ASPECT_RATIO_TOLERANCE: f64 = 0.02;
+
+#[derive(Debug, Clone)]
+pub struct VideoMetadata {
+    //seconds
+    pub duration: f64,
+    //as displayed, rotated videos have width and height swapped
+    pub width: i32,
+    pub height: i32,
+    pub codec: String,
+    //bits per second over the whole file
+    pub bitrate: i64,
+    pub file_size: i64,
+}
+
+/// Reads the metadata of an MP4/MOV container, files that cannot be parsed
+/// or that have no video track are rejected.
+pub fn probe(path: &Path, file_name: &str) -> Result<VideoMetadata, RunTimeError> {
+    let unsupported = |reason: String| {
+        RunTimeError::UnsupportedMediaType(format!(
+            "{} is not a valid video: {}",
+            file_name, reason
+        ))
+    };
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mp4 = mp4::Mp4Reader::read_header(BufReader::new(file), file_size)
+        .map_err(|e| unsupported(e.to_string()))?;
+    let track = mp4
+        .tracks()
+        .values()
+        .find(|track| matches!(track.track_type(), Ok(TrackType::Video)))
+        .ok_or_else(|| unsupported("no video track".to_string()))?;
+    let duration = mp4.duration().as_secs_f64();
+    if duration <= 0.0 {
+        return Err(unsupported("zero duration".to_string()));
+    }
+    let (mut width, mut height) = (track.width() as i32, track.height() as i32);
+    //a 90 or 270 degree rotation matrix has a zero `a` entry
+    if track.trak.tkhd.matrix.a == 0 && track.trak.tkhd.matrix.b != 0 {
+        std::mem::swap(&mut width, &mut height);
+    }
+    let codec = track
+        .box_type()
+        .map(|fourcc| fourcc.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    Ok(VideoMetadata {
+        duration,
+        width,
+        height,
+        codec,
+        bitrate: (file_size as f64 * 8.0 / duration) as i64,
+        file_size: file_size as i64,
+    })
+}
+
+//"9:16" -> 0.5625
+fn parse_ratio(ratio: &str) -> Option<f64> {
+    let (width, height) = ratio.trim().split_once(':')?;
+    let width: f64 = width.trim().parse().ok()?;
+    let height: f64 = height.trim().parse().ok()?;
+    (width > 0.0 && height > 0.0).then(|| width / height)
+}
+
+/// Checks the group constraints: `min_duration`/`max_duration` in seconds
+/// (0 for no limit) and `aspect_ratio`, a comma separated list like `9:16,1:1`.
+pub fn check_group(
+    meta: &VideoMetadata,
+    group: &GroupDetails,
+    file_name: &str,
+) -> Result<(), RunTimeError> {
+    if let Some(min_duration) = group.min_duration.filter(|d| *d > 0.0) {
+        if meta.duration < min_duration {
+            return Err(RunTimeError::Unprocessable(format!(
+                "{} is {:.1}s long, group {} requires at least {}s",
+                file_name, meta.duration, group.name, min_duration
+            )));
+        }
+    }
+    if let Some(max_duration) = group.max_duration.filter(|d| *d > 0.0) {
+        if meta.duration > max_duration {
+            return Err(RunTimeError::Unprocessable(format!(
+                "{} is {:.1}s long, group {} allows at most {}s",
+                file_name, meta.duration, group.name, max_duration
+            )));
+        }
+    }
+    if let Some(aspect_ratio) = group
+        .aspect_ratio
+        .as_deref()
+        .filter(|r| !r.trim().is_empty())
+    {
+        if meta.height <= 0 {
+            return Err(RunTimeError::Unprocessable(format!(
+                "{} has no resolution",
+                file_name
+            )));
+        }
+        let actual = meta.width as f64 / meta.height as f64;
+        let matches = aspect_ratio
+            .split(',')
+            .filter_map(parse_ratio)
+            .any(|ratio| ((actual - ratio) / ratio).abs() <= ASPECT_RATIO_TOLERANCE);
+        if !matches {
+            return Err(RunTimeError::Unprocessable(format!(
+                "{} is {}x{}, group {} requires aspect ratio {}",
+                file_name, meta.width, meta.height, group.name, aspect_ratio
+            )));
+        }
+    }
+    Ok(())
+}